@@ -0,0 +1,96 @@
+// A sample shared library plugin, see `src/commands/plugin.rs` for the ABI.
+//
+// build it and drop it in the plugins folder to try it out:
+//   cargo build --example native_plugin
+//   cp target/debug/examples/libnative_plugin.so ~/.term-rs/plugins/
+//
+// it's loaded into the shell itself, so everything it hands over has to
+// stay put for as long as it's loaded. statics are the easy way to do that.
+
+use std::ffi::{c_char, CStr};
+
+use terminal_rs::commands::plugin::{PluginCommandDecl, PluginDecl, PluginHost, PLUGIN_API_VERSION};
+
+/// the declarations are all raw pointers, which rust won't share between
+/// threads on its own. they only ever point at other statics.
+struct Shared<T>(T);
+
+unsafe impl<T> Sync for Shared<T> {}
+
+static COMMANDS: Shared<[PluginCommandDecl; 3]> = Shared([
+    PluginCommandDecl {
+        name: c"greet".as_ptr(),
+        desc: c"say hello to everyone named".as_ptr(),
+        docs: std::ptr::null(),
+        execute: greet,
+    },
+    PluginCommandDecl {
+        name: c"here".as_ptr(),
+        desc: c"print the working directory".as_ptr(),
+        docs: std::ptr::null(),
+        execute: here,
+    },
+    PluginCommandDecl {
+        name: c"grumble".as_ptr(),
+        desc: std::ptr::null(),
+        docs: std::ptr::null(),
+        execute: grumble,
+    },
+]);
+
+static PLUGIN: Shared<PluginDecl> = Shared(PluginDecl {
+    api_version: PLUGIN_API_VERSION,
+    name: c"native".as_ptr(),
+    version: c"0.2.0".as_ptr(),
+    commands: &COMMANDS.0 as *const PluginCommandDecl,
+    command_count: 3,
+});
+
+#[no_mangle]
+pub extern "C" fn termrs_plugin_v1() -> *const PluginDecl {
+    &PLUGIN.0
+}
+
+fn write_out(host: *const PluginHost, text: &str) {
+    // SAFETY: the shell hands every command a valid host.
+    let host = unsafe { &*host };
+    (host.write_out)(text.as_ptr(), text.len());
+}
+
+fn args<'a>(argc: usize, argv: *const *const c_char) -> Vec<&'a str> {
+    if argc == 0 {
+        return Vec::new();
+    }
+    // SAFETY: the shell passes `argc` valid utf-8 C strings.
+    unsafe { std::slice::from_raw_parts(argv, argc) }
+        .iter()
+        .map(|arg| unsafe { CStr::from_ptr(*arg) }.to_str().unwrap_or_default())
+        .collect()
+}
+
+extern "C" fn greet(host: *const PluginHost, argc: usize, argv: *const *const c_char) -> i32 {
+    let names = args(argc, argv);
+    if names.is_empty() {
+        write_out(host, "hello, nobody\n");
+    }
+    for name in names {
+        write_out(host, &format!("hello, {}\n", name));
+    }
+    0
+}
+
+extern "C" fn here(host: *const PluginHost, _argc: usize, _argv: *const *const c_char) -> i32 {
+    // SAFETY: the shell hands every command a valid host, with a
+    // nul terminated cwd.
+    let cwd = unsafe { CStr::from_ptr((*host).cwd) };
+    write_out(host, &format!("{}\n", cwd.to_string_lossy()));
+    0
+}
+
+extern "C" fn grumble(host: *const PluginHost, _argc: usize, _argv: *const *const c_char) -> i32 {
+    // SAFETY: the shell hands every command a valid host.
+    let host = unsafe { &*host };
+    let text = "grr\n";
+    (host.write_err)(text.as_ptr(), text.len());
+    4
+}
@@ -0,0 +1,21 @@
+// A shared library plugin built for a newer version of the plugin api
+// than this shell speaks, it should be turned away when loaded.
+
+use terminal_rs::commands::plugin::{PluginDecl, PLUGIN_API_VERSION};
+
+struct Shared<T>(T);
+
+unsafe impl<T> Sync for Shared<T> {}
+
+static PLUGIN: Shared<PluginDecl> = Shared(PluginDecl {
+    api_version: PLUGIN_API_VERSION + 1,
+    name: c"newer".as_ptr(),
+    version: c"9.0.0".as_ptr(),
+    commands: std::ptr::null(),
+    command_count: 0,
+});
+
+#[no_mangle]
+pub extern "C" fn termrs_plugin_v1() -> *const PluginDecl {
+    &PLUGIN.0
+}
@@ -0,0 +1,91 @@
+// A sample out-of-process plugin, see `src/commands/rpc.rs` for the protocol.
+//
+// build it and drop it in the plugins folder to try it out:
+//   cargo build --example rpc_plugin
+//   cp target/debug/examples/rpc_plugin ~/.term-rs/plugins/
+//
+// any language works, all a plugin needs is to read json lines from stdin
+// and write them to stdout.
+
+use std::io::{BufRead, Write};
+
+use serde_json::{json, Value};
+
+fn send(message: Value) {
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", message);
+    let _ = stdout.flush();
+}
+
+fn output(stream: &str, data: &str) {
+    send(json!({
+        "jsonrpc": "2.0",
+        "method": "output",
+        "params": { "stream": stream, "data": data },
+    }));
+}
+
+fn describe() -> Value {
+    json!({
+        "name": "sample",
+        "version": env!("CARGO_PKG_VERSION"),
+        "commands": [
+            {
+                "name": "shout",
+                "desc": "repeat the arguments, loudly",
+                "docs": "\n        shout <words...>\n\n        prints the words in upper case.\n        ",
+            },
+            {
+                "name": "getenv",
+                "desc": "print an environment variable",
+                "docs": "\n        getenv <name>\n\n        prints the variable as the shell sees it.\n        ",
+            },
+        ],
+    })
+}
+
+/// run a command, returning its exit status.
+fn execute(params: &Value) -> i32 {
+    let args: Vec<&str> = params["args"]
+        .as_array()
+        .map(|args| args.iter().filter_map(|arg| arg.as_str()).collect())
+        .unwrap_or_default();
+
+    match params["command"].as_str() {
+        Some("shout") => {
+            output("stdout", &format!("{}!\n", args.join(" ").to_uppercase()));
+            0
+        },
+        Some("getenv") => match args.first().and_then(|name| params["env"][name].as_str()) {
+            Some(value) => {
+                output("stdout", &format!("{}\n", value));
+                0
+            },
+            None => {
+                output("stderr", "no such variable\n");
+                1
+            }
+        },
+        _ => {
+            output("stderr", "unknown command\n");
+            127
+        }
+    }
+}
+
+fn main() {
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let Ok(message) = serde_json::from_str::<Value>(&line) else { continue };
+
+        let result = match message["method"].as_str() {
+            Some("describe") => describe(),
+            Some("execute") => json!({ "status": execute(&message["params"]) }),
+            Some("shutdown") => break,
+            // notifications like "cancel" don't need an answer.
+            _ => continue,
+        };
+
+        send(json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }));
+    }
+}
@@ -1,29 +1,756 @@
-use std::collections::HashMap;
+// Declarative arguments.
+//
+// Builtins describe what they take with an `ArgSpec` (see `Cmd::args`).
+// The same spec parses the arguments, reports usage errors the same way
+// for every command, completes them and writes the text `man` and `help`
+// show, so none of that has to be done by hand.
+//
+// Parsing follows GNU: flags can go anywhere, short flags group (`-cU`),
+// values go after the option or are attached to it (`-n 5`, `-n5`,
+// `--last 5`, `--last=5`), and everything after `--` is an argument.
 
-/// saves information about flags used in the arguments.
-pub struct ArgInfo {
-    _flags: HashMap<char, bool>,
+use std::{collections::HashMap, str::FromStr};
+
+use crate::core::completion::{Completion, complete_commands, complete_flags, complete_options, complete_paths};
+use super::{Cmd, Context};
+use crate::error::UniError;
+
+/// what a value has to look like.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// anything at all.
+    Text,
+    /// a whole number, which can be negative.
+    Int,
+    /// a whole number that's zero or more.
+    Count,
+    /// a file or a directory.
+    Path,
+    /// a directory.
+    Dir,
+    /// the name of a command.
+    Command,
+    /// one of a fixed set of words, a list of (word, description).
+    OneOf(&'static [(&'static str, &'static str)]),
+}
+
+impl Kind {
+    fn check(&self, value: &str) -> Result<(), String> {
+        match self {
+            Kind::Int => value
+                .parse::<i64>()
+                .map(|_| ())
+                .map_err(|_| format!("expected a number, got \"{}\"", value)),
+            Kind::Count => value
+                .parse::<usize>()
+                .map(|_| ())
+                .map_err(|_| format!("expected a positive number, got \"{}\"", value)),
+            Kind::OneOf(words) if !words.iter().any(|(word, _)| *word == value) => {
+                let words: Vec<String> = words.iter().map(|(word, _)| format!("\"{}\"", word)).collect();
+                Err(format!("expected one of {}, got \"{}\"", words.join(", "), value))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn complete(&self, ctx: &Context<'_>, word: &str) -> Vec<Completion> {
+        match self {
+            Kind::Path => complete_paths(ctx, word, false),
+            Kind::Dir => complete_paths(ctx, word, true),
+            Kind::Command => complete_commands(ctx, word),
+            Kind::OneOf(words) => words
+                .iter()
+                .filter(|(value, _)| value.starts_with(word))
+                .map(|(value, desc)| Completion::with_desc(*value, *desc))
+                .collect(),
+            Kind::Text | Kind::Int | Kind::Count => Vec::new(),
+        }
+    }
+}
+
+/// a flag, or an option when it takes a value.
+struct Flag {
+    short: Option<char>,
+    long: Option<&'static str>,
+    /// (placeholder, kind) for options.
+    value: Option<(&'static str, Kind)>,
+    help: &'static str,
+}
+
+impl Flag {
+    fn display(&self) -> String {
+        let mut names = Vec::new();
+        if let Some(short) = self.short {
+            names.push(format!("-{}", short));
+        }
+        if let Some(long) = self.long {
+            names.push(format!("--{}", long));
+        }
+        match self.value {
+            Some((placeholder, _)) => format!("{} {}", names.join(", "), placeholder),
+            None => names.join(", "),
+        }
+    }
+
+    /// every way it can be written, which is also how it's looked up.
+    fn spellings(&self) -> Vec<String> {
+        let short = self.short.map(|short| format!("-{}", short));
+        let long = self.long.map(|long| format!("--{}", long));
+        short.into_iter().chain(long).collect()
+    }
+
+    /// how it's written on its own, `-n` or `--json`.
+    fn spelling(&self) -> String {
+        self.spellings().remove(0)
+    }
+}
+
+struct Positional {
+    name: &'static str,
+    kind: Kind,
+    required: bool,
+    /// takes every argument that's left.
+    repeats: bool,
+    help: &'static str,
+}
+
+impl Positional {
+    fn display(&self) -> String {
+        match (self.required, self.repeats) {
+            (true, false) => format!("<{}>", self.name),
+            (false, false) => format!("[{}]", self.name),
+            (_, true) => format!("[{}...]", self.name),
+        }
+    }
 }
 
-impl ArgInfo {
-    pub fn new(args: &Vec<&str>) -> ArgInfo {
-        let mut map = HashMap::new();
+/// everything a command accepts.
+#[derive(Default)]
+pub struct ArgSpec {
+    flags: Vec<Flag>,
+    positionals: Vec<Positional>,
+}
+
+impl ArgSpec {
+    pub fn new() -> ArgSpec {
+        ArgSpec::default()
+    }
+
+    /// a flag, `names` is how it's written, like `"-b"`, `"--json"` or
+    /// `"-a, --all"`.
+    pub fn flag(mut self, names: &'static str, help: &'static str) -> ArgSpec {
+        self.flags.push(Self::make_flag(names, None, help));
+        self
+    }
+
+    /// a flag that takes a value, `-n N`. `placeholder` is what the value
+    /// is called in the usage.
+    pub fn option(mut self, names: &'static str, placeholder: &'static str, kind: Kind, help: &'static str) -> ArgSpec {
+        self.flags.push(Self::make_flag(names, Some((placeholder, kind)), help));
+        self
+    }
+
+    /// an argument that has to be there.
+    pub fn arg(self, name: &'static str, kind: Kind, help: &'static str) -> ArgSpec {
+        self.positional(name, kind, true, false, help)
+    }
+
+    /// an argument that can be left off, these go after the required ones.
+    pub fn optional(self, name: &'static str, kind: Kind, help: &'static str) -> ArgSpec {
+        self.positional(name, kind, false, false, help)
+    }
+
+    /// any number of arguments, this has to be last.
+    pub fn repeated(self, name: &'static str, kind: Kind, help: &'static str) -> ArgSpec {
+        self.positional(name, kind, false, true, help)
+    }
+
+    fn positional(mut self, name: &'static str, kind: Kind, required: bool, repeats: bool, help: &'static str) -> ArgSpec {
+        debug_assert!(
+            !self.positionals.last().is_some_and(|p| p.repeats),
+            "nothing can come after a repeated argument"
+        );
+        self.positionals.push(Positional { name, kind, required, repeats, help });
+        self
+    }
+
+    fn make_flag(names: &'static str, value: Option<(&'static str, Kind)>, help: &'static str) -> Flag {
+        let mut short = None;
+        let mut long = None;
+        for name in names.split(',').map(str::trim) {
+            if let Some(name) = name.strip_prefix("--") {
+                long = Some(name);
+            }
+            else if let Some(name) = name.strip_prefix('-') {
+                short = name.chars().next();
+            }
+        }
+
+        debug_assert!(short.is_some() || long.is_some(), "\"{}\" isn't a flag", names);
+        Flag { short, long, value, help }
+    }
 
-        for elem in args.iter() {
-            if elem.starts_with('-') {
-                let all_after_tac = &elem[1..=elem.len()-1];
-                for c in all_after_tac.chars() {
-                    map.insert(c, true);
+    fn find_short(&self, flag: char) -> Option<&Flag> {
+        self.flags.iter().find(|f| f.short == Some(flag))
+    }
+
+    fn find_long(&self, name: &str) -> Option<&Flag> {
+        self.flags.iter().find(|f| f.long == Some(name))
+    }
+
+    /// `-1` is a number unless the command has a flag called `1`.
+    fn is_number(&self, arg: &str) -> bool {
+        arg[1..].starts_with(|c: char| c.is_ascii_digit())
+            && arg[1..].parse::<f64>().is_ok()
+            && !arg[1..].chars().any(|c| self.find_short(c).is_some())
+    }
+
+    /// split `raw` up into flags, options and positionals.
+    pub fn parse<'a>(&self, command: &str, raw: &[&'a str]) -> Result<Args<'a>, UniError> {
+        let usage_error = |message: String| {
+            UniError::Usage(format!("{}: {}\n{}", command, message, self.usage(command)))
+                .with_hint(Some(format!("see `man {}` for more.", command)))
+        };
+
+        let mut args = Args {
+            raw: raw.to_vec(),
+            found: HashMap::new(),
+            positionals: Vec::new(),
+        };
+        let mut loose = Vec::new();
+        let mut rest = raw.iter();
+
+        // the value of an option, either attached to it (`-n5`,
+        // `--last=5`) or the next argument.
+        let take_value = |flag: &Flag, attached: Option<&'a str>, rest: &mut std::slice::Iter<'_, &'a str>| -> Result<&'a str, UniError> {
+            let (placeholder, kind) = flag.value.expect("only options take values");
+            let value = match attached.or_else(|| rest.next().copied()) {
+                Some(value) => value,
+                None => return Err(usage_error(format!("{} expects {}", flag.spelling(), placeholder))),
+            };
+            kind.check(value).map_err(|e| usage_error(format!("{}: {}", flag.spelling(), e)))?;
+            Ok(value)
+        };
+
+        while let Some(arg) = rest.next() {
+            // everything after `--` is an argument, even if it starts with `-`.
+            if *arg == "--" {
+                loose.extend(rest.by_ref());
+                break;
+            }
+
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, attached) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (long, None),
+                };
+                let Some(flag) = self.find_long(name) else {
+                    return Err(usage_error(format!("unknown option \"--{}\"", name)));
+                };
+                let value = match (flag.value, attached) {
+                    (Some(_), _) => take_value(flag, attached, &mut rest)?,
+                    (None, Some(_)) => return Err(usage_error(format!("--{} doesn't take a value", name))),
+                    (None, None) => "",
+                };
+                args.add(flag, value);
+                continue;
+            }
+
+            if arg.len() < 2 || !arg.starts_with('-') || self.is_number(arg) {
+                loose.push(*arg);
+                continue;
+            }
+
+            // a group of short flags, `-cU`. once one of them takes a
+            // value the rest of the group is that value, `-n5`.
+            let group = &arg[1..];
+            for (i, c) in group.char_indices() {
+                let Some(flag) = self.find_short(c) else {
+                    return Err(usage_error(format!("unknown flag \"-{}\"", c)));
+                };
+                if flag.value.is_none() {
+                    args.add(flag, "");
+                    continue;
                 }
+
+                let attached = Some(&group[i + c.len_utf8()..]).filter(|value| !value.is_empty());
+                let value = take_value(flag, attached, &mut rest)?;
+                args.add(flag, value);
+                break;
+            }
+        }
+
+        let mut loose = loose.into_iter();
+        for positional in &self.positionals {
+            let values: Vec<&str> = if positional.repeats {
+                loose.by_ref().collect()
+            }
+            else {
+                loose.next().into_iter().collect()
+            };
+
+            if values.is_empty() && positional.required {
+                return Err(usage_error(format!("missing {}", positional.display())));
+            }
+            for value in values {
+                positional.kind
+                    .check(value)
+                    .map_err(|e| usage_error(format!("{}: {}", positional.name, e)))?;
+                args.positionals.push((positional.name, value));
             }
         }
 
-        ArgInfo {
-            _flags: map
+        if let Some(extra) = loose.next() {
+            return Err(usage_error(format!("unexpected argument \"{}\"", extra)));
         }
-    } 
 
-    pub fn has_flag(&self, flag: char) -> bool {
-        self._flags.contains_key(&flag)
+        Ok(args)
     }
-}
\ No newline at end of file
+
+    /// a one line summary, `usage: where [-a] <name>`.
+    pub fn usage(&self, command: &str) -> String {
+        let mut parts = vec![format!("usage: {}", command)];
+
+        let grouped: String = self.flags
+            .iter()
+            .filter(|f| f.value.is_none())
+            .filter_map(|f| f.short)
+            .collect();
+        if !grouped.is_empty() {
+            parts.push(format!("[-{}]", grouped));
+        }
+
+        for flag in &self.flags {
+            match (flag.short, flag.value) {
+                (Some(_), None) => {},
+                (_, Some((placeholder, _))) => parts.push(format!("[{} {}]", flag.spelling(), placeholder)),
+                (None, None) => parts.push(format!("[{}]", flag.spelling())),
+            }
+        }
+
+        parts.extend(self.positionals.iter().map(Positional::display));
+        parts.join(" ")
+    }
+
+    /// every flag and argument along with what it does.
+    pub fn describe(&self) -> String {
+        let mut rows = Vec::new();
+        for positional in &self.positionals {
+            rows.push((positional.display(), positional.help));
+            if let Kind::OneOf(words) = positional.kind {
+                rows.extend(words.iter().map(|(word, desc)| (format!("  {}", word), *desc)));
+            }
+        }
+        rows.extend(self.flags.iter().map(|flag| (flag.display(), flag.help)));
+
+        let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        rows.iter()
+            .map(|(name, help)| format!("  {:width$}  {}", name, help, width = width))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// complete `args[cursor]`.
+    pub fn complete(&self, ctx: &Context<'_>, args: &[&str], cursor: usize) -> Vec<Completion> {
+        let word = args[cursor];
+
+        // the value of an option.
+        if let Some(previous) = cursor.checked_sub(1).map(|i| args[i]) {
+            if let Some(kind) = self.value_kind(previous) {
+                return kind.complete(ctx, word);
+            }
+        }
+
+        let options_ended = args[..cursor].contains(&"--");
+        if word.starts_with('-') && !self.is_number(word) && !options_ended {
+            // add to a group of short flags, `-c` -> `-cU`.
+            if word.len() > 1 && !word.starts_with("--") {
+                let flags: Vec<(char, &str)> = self.flags
+                    .iter()
+                    .filter(|f| f.value.is_none())
+                    .filter_map(|f| f.short.map(|short| (short, f.help)))
+                    .collect();
+                return complete_flags(word, &flags);
+            }
+
+            let options: Vec<(String, &str)> = self.flags
+                .iter()
+                .flat_map(|f| {
+                    let short = f.short.map(|short| (format!("-{}", short), f.help));
+                    let long = f.long.map(|long| (format!("--{}", long), f.help));
+                    short.into_iter().chain(long)
+                })
+                .collect();
+            let options: Vec<(&str, &str)> = options.iter().map(|(name, help)| (name.as_str(), *help)).collect();
+            return complete_options(word, &options);
+        }
+
+        // which positional this is, skipping over flags and their values.
+        let mut index = 0;
+        let mut skip_value = false;
+        let mut options_ended = false;
+        for arg in &args[..cursor] {
+            if std::mem::take(&mut skip_value) {
+                continue;
+            }
+            if !options_ended && *arg == "--" {
+                options_ended = true;
+                continue;
+            }
+            if !options_ended && arg.starts_with('-') && arg.len() > 1 && !self.is_number(arg) {
+                skip_value = self.value_kind(arg).is_some();
+                continue;
+            }
+            index += 1;
+        }
+
+        let positional = self.positionals
+            .get(index)
+            .or(self.positionals.last().filter(|p| p.repeats));
+        match positional {
+            Some(positional) => positional.kind.complete(ctx, word),
+            None => Vec::new(),
+        }
+    }
+
+    /// the kind of value `arg` expects after it, when it's an option
+    /// that doesn't already have its value attached.
+    fn value_kind(&self, arg: &str) -> Option<Kind> {
+        if let Some(name) = arg.strip_prefix("--") {
+            return self.find_long(name).and_then(|f| f.value).map(|(_, kind)| kind);
+        }
+
+        let group = arg.strip_prefix('-')?;
+        let (i, c) = group.char_indices().find(|(_, c)| self.find_short(*c).is_some_and(|f| f.value.is_some()))?;
+        if i + c.len_utf8() < group.len() {
+            return None;
+        }
+        self.find_short(c).and_then(|f| f.value).map(|(_, kind)| kind)
+    }
+}
+
+/// parse the arguments to `cmd` with its spec. commands without one get
+/// every argument as is.
+pub fn parse<'a>(cmd: &dyn Cmd, raw: &[&'a str]) -> Result<Args<'a>, UniError> {
+    match cmd.args() {
+        Some(spec) => spec.parse(cmd.name(), raw),
+        None => Ok(Args {
+            raw: raw.to_vec(),
+            found: HashMap::new(),
+            positionals: raw.iter().map(|arg| ("args", *arg)).collect(),
+        }),
+    }
+}
+
+/// arguments that have been checked against an `ArgSpec`.
+pub struct Args<'a> {
+    raw: Vec<&'a str>,
+    /// every value given to each flag, flags without values get "".
+    found: HashMap<String, Vec<&'a str>>,
+    positionals: Vec<(&'static str, &'a str)>,
+}
+
+impl<'a> Args<'a> {
+    fn add(&mut self, flag: &Flag, value: &'a str) {
+        for spelling in flag.spellings() {
+            self.found.entry(spelling).or_default().push(value);
+        }
+    }
+
+    /// was the flag given, `name` is any of its spellings, `-a` or `--all`.
+    pub fn has(&self, name: &str) -> bool {
+        self.found.contains_key(name)
+    }
+
+    /// the value of an option (`-n`) or argument (`name`), the last one
+    /// if it was given more than once.
+    pub fn value(&self, name: &str) -> Option<&'a str> {
+        self.values(name).pop()
+    }
+
+    /// every value of an option or argument.
+    pub fn values(&self, name: &str) -> Vec<&'a str> {
+        match self.found.get(name) {
+            Some(values) => values.clone(),
+            None => self.positionals
+                .iter()
+                .filter(|(positional, _)| *positional == name)
+                .map(|(_, value)| *value)
+                .collect(),
+        }
+    }
+
+    /// a value as a number, it's already been checked by the spec.
+    pub fn number<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.value(name).and_then(|value| value.parse().ok())
+    }
+
+    /// the arguments exactly as they were typed.
+    pub fn raw(&self) -> &[&'a str] {
+        &self.raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::lexer;
+
+    /// parse `line` the way a command typed at the prompt would be.
+    fn parse_line(spec: &ArgSpec, line: &str, check: impl FnOnce(Result<Args<'_>, UniError>)) {
+        let words = lexer::words(line);
+        let raw: Vec<&str> = words[1..].iter().map(String::as_str).collect();
+        check(spec.parse(&words[0], &raw));
+    }
+
+    fn ok<'a>(spec: &ArgSpec, raw: &[&'a str]) -> Args<'a> {
+        match spec.parse("t", raw) {
+            Ok(args) => args,
+            Err(e) => panic!("{:?} didn't parse: {}", raw, e),
+        }
+    }
+
+    /// the first line of the error, the rest is the usage.
+    fn error(spec: &ArgSpec, raw: &[&str]) -> String {
+        match spec.parse("t", raw) {
+            Ok(_) => panic!("{:?} should have failed", raw),
+            Err(e) => e.to_string().lines().next().unwrap_or("").to_string(),
+        }
+    }
+
+    /// flags, an option and any number of files.
+    fn options() -> ArgSpec {
+        ArgSpec::new()
+            .flag("-a, --all", "everything")
+            .flag("-c", "c")
+            .flag("-U", "u")
+            .flag("--json", "as json")
+            .option("-n, --last", "N", Kind::Int, "how many")
+            .repeated("files", Kind::Text, "the files")
+    }
+
+    #[test]
+    fn repeated_spaces_dont_make_empty_arguments() {
+        let mkdir = ArgSpec::new().repeated("dirs", Kind::Path, "the directories to make");
+        parse_line(&mkdir, "mkdir  x   y", |args| assert_eq!(args.unwrap().values("dirs"), vec!["x", "y"]));
+
+        let ls = ArgSpec::new().flag("-a", "show hidden files").optional("dir", Kind::Dir, "the directory to list");
+        parse_line(&ls, "ls  dir", |args| {
+            let args = args.unwrap();
+            assert_eq!(args.value("dir"), Some("dir"));
+            assert!(!args.has("-a"));
+        });
+        parse_line(&ls, "ls  -a  dir", |args| {
+            let args = args.unwrap();
+            assert_eq!(args.value("dir"), Some("dir"));
+            assert!(args.has("-a"));
+        });
+
+        let help = ArgSpec::new().flag("-b", "only builtins").optional("command", Kind::Command, "the command");
+        parse_line(&help, "help  cd", |args| assert_eq!(args.unwrap().value("command"), Some("cd")));
+    }
+
+    #[test]
+    fn quoted_arguments_stay_whole() {
+        let echo = ArgSpec::new().repeated("text", Kind::Text, "what to print");
+        parse_line(&echo, r#"echo "hi there""#, |args| {
+            let args = args.unwrap();
+            assert_eq!(args.values("text"), vec!["hi there"]);
+            assert_eq!(args.raw(), ["hi there"]);
+        });
+    }
+
+    #[test]
+    fn long_options() {
+        for raw in [&["--last=5"][..], &["--last", "5"]] {
+            let args = ok(&options(), raw);
+            assert_eq!(args.value("-n"), Some("5"));
+            assert_eq!(args.value("--last"), Some("5"));
+            assert_eq!(args.number::<i64>("-n"), Some(5));
+        }
+
+        let args = ok(&options(), &["--all", "--json"]);
+        assert!(args.has("-a") && args.has("--all") && args.has("--json"));
+        // an empty value after `=` is still a value.
+        assert!(error(&options(), &["--last="]).contains("expected a number, got \"\""));
+    }
+
+    #[test]
+    fn short_options_and_groups() {
+        assert_eq!(ok(&options(), &["-n5"]).value("-n"), Some("5"));
+        assert_eq!(ok(&options(), &["-n", "5"]).value("-n"), Some("5"));
+        // the value can look like a flag.
+        assert_eq!(ok(&options(), &["-n", "-5"]).value("-n"), Some("-5"));
+
+        let args = ok(&options(), &["-cU"]);
+        assert!(args.has("-c") && args.has("-U") && !args.has("-a"));
+
+        // the rest of a group after an option is its value.
+        let args = ok(&options(), &["-cn12"]);
+        assert!(args.has("-c"));
+        assert_eq!(args.value("--last"), Some("12"));
+
+        let args = ok(&options(), &["-cn", "3", "file"]);
+        assert_eq!(args.value("-n"), Some("3"));
+        assert_eq!(args.values("files"), vec!["file"]);
+    }
+
+    #[test]
+    fn repeated_options_keep_every_value() {
+        let args = ok(&options(), &["-n", "1", "--last=2", "-n3"]);
+        assert_eq!(args.values("-n"), vec!["1", "2", "3"]);
+        assert_eq!(args.value("-n"), Some("3"));
+    }
+
+    #[test]
+    fn flags_can_go_after_arguments() {
+        let args = ok(&options(), &["a", "-a", "b", "--last", "2", "c"]);
+        assert!(args.has("--all"));
+        assert_eq!(args.value("-n"), Some("2"));
+        assert_eq!(args.values("files"), vec!["a", "b", "c"]);
+        assert_eq!(args.raw(), ["a", "-a", "b", "--last", "2", "c"]);
+    }
+
+    #[test]
+    fn double_dash_ends_the_flags() {
+        let args = ok(&options(), &["-a", "--", "-c", "--json", "--"]);
+        assert!(args.has("-a"));
+        assert!(!args.has("-c") && !args.has("--json"));
+        assert_eq!(args.values("files"), vec!["-c", "--json", "--"]);
+    }
+
+    #[test]
+    fn a_lone_dash_is_an_argument() {
+        assert_eq!(ok(&options(), &["-"]).values("files"), vec!["-"]);
+    }
+
+    #[test]
+    fn negative_numbers_are_arguments() {
+        let cfg = ArgSpec::new()
+            .flag("-a", "a")
+            .repeated("value", Kind::Int, "what to set it to");
+        assert_eq!(ok(&cfg, &["-1"]).values("value"), vec!["-1"]);
+        assert_eq!(ok(&cfg, &["-a", "-25", "3"]).values("value"), vec!["-25", "3"]);
+
+        // unless the command has a flag with that name.
+        let ls = ArgSpec::new().flag("-1", "one per line").repeated("value", Kind::Int, "values");
+        let args = ok(&ls, &["-1"]);
+        assert!(args.has("-1"));
+        assert!(args.values("value").is_empty());
+
+        // and only whole numbers count, `-1a` is a group of flags.
+        assert_eq!(error(&cfg, &["-1a"]), "t: unknown flag \"-1\"");
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error(&options(), &["--nope"]), "t: unknown option \"--nope\"");
+        assert_eq!(error(&options(), &["--nope=3"]), "t: unknown option \"--nope\"");
+        assert_eq!(error(&options(), &["-x"]), "t: unknown flag \"-x\"");
+        assert_eq!(error(&options(), &["-cx"]), "t: unknown flag \"-x\"");
+        assert_eq!(error(&options(), &["--json=yes"]), "t: --json doesn't take a value");
+        assert_eq!(error(&options(), &["-n"]), "t: -n expects N");
+        assert_eq!(error(&options(), &["a", "--last"]), "t: -n expects N");
+        assert_eq!(error(&options(), &["-n", "x"]), "t: -n: expected a number, got \"x\"");
+        assert_eq!(error(&options(), &["--last=x"]), "t: -n: expected a number, got \"x\"");
+    }
+
+    #[test]
+    fn errors_are_usage_errors_with_a_hint() {
+        let e = options().parse("t", &["--nope"]).err().unwrap();
+        assert_eq!(e.code(), "usage");
+        assert_eq!(e.hint(), Some("see `man t` for more."));
+        assert!(e.to_string().ends_with("usage: t [-acU] [--json] [-n N] [files...]"), "{}", e);
+    }
+
+    const SHAPES: &[(&str, &str)] = &[("circle", "round"), ("square", "four sides")];
+
+    /// one of each kind of positional.
+    fn positionals() -> ArgSpec {
+        ArgSpec::new()
+            .arg("shape", Kind::OneOf(SHAPES), "what to draw")
+            .arg("size", Kind::Count, "how big")
+            .optional("offset", Kind::Int, "where")
+            .repeated("labels", Kind::Text, "what to write on it")
+    }
+
+    #[test]
+    fn positionals_are_filled_in_order() {
+        let args = ok(&positionals(), &["circle", "3"]);
+        assert_eq!(args.value("shape"), Some("circle"));
+        assert_eq!(args.number::<usize>("size"), Some(3));
+        assert_eq!(args.value("offset"), None);
+        assert!(args.values("labels").is_empty());
+
+        let args = ok(&positionals(), &["square", "0", "-2", "a", "b"]);
+        assert_eq!(args.number::<i64>("offset"), Some(-2));
+        assert_eq!(args.values("labels"), vec!["a", "b"]);
+        assert!(!args.has("shape"));
+    }
+
+    #[test]
+    fn missing_and_extra_positionals() {
+        assert_eq!(error(&positionals(), &[]), "t: missing <shape>");
+        assert_eq!(error(&positionals(), &["circle"]), "t: missing <size>");
+
+        let one = ArgSpec::new().optional("dir", Kind::Dir, "where");
+        assert!(ok(&one, &[]).value("dir").is_none());
+        assert_eq!(error(&one, &["a", "b"]), "t: unexpected argument \"b\"");
+        assert_eq!(error(&ArgSpec::new(), &["a"]), "t: unexpected argument \"a\"");
+    }
+
+    #[test]
+    fn values_are_checked_against_their_kind() {
+        assert_eq!(error(&positionals(), &["triangle", "1"]), "t: shape: expected one of \"circle\", \"square\", got \"triangle\"");
+        assert_eq!(error(&positionals(), &["circle", "-1"]), "t: size: expected a positive number, got \"-1\"");
+        assert_eq!(error(&positionals(), &["circle", "big"]), "t: size: expected a positive number, got \"big\"");
+        assert_eq!(error(&positionals(), &["circle", "1", "1.5"]), "t: offset: expected a number, got \"1.5\"");
+
+        // text, paths and commands take anything.
+        for kind in [Kind::Text, Kind::Path, Kind::Dir, Kind::Command] {
+            let spec = ArgSpec::new().arg("x", kind, "anything");
+            assert_eq!(ok(&spec, &["a b/c?!"]).value("x"), Some("a b/c?!"));
+        }
+    }
+
+    #[test]
+    fn usage_lists_flags_then_arguments() {
+        assert_eq!(ArgSpec::new().usage("t"), "usage: t");
+        assert_eq!(positionals().usage("draw"), "usage: draw <shape> <size> [offset] [labels...]");
+        assert_eq!(options().usage("t"), "usage: t [-acU] [--json] [-n N] [files...]");
+    }
+
+    #[test]
+    fn describe_lines_up_the_help() {
+        assert_eq!(positionals().describe(), [
+            "  <shape>      what to draw",
+            "    circle     round",
+            "    square     four sides",
+            "  <size>       how big",
+            "  [offset]     where",
+            "  [labels...]  what to write on it",
+        ].join("\n"));
+
+        assert_eq!(options().describe(), [
+            "  [files...]    the files",
+            "  -a, --all     everything",
+            "  -c            c",
+            "  -U            u",
+            "  --json        as json",
+            "  -n, --last N  how many",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn every_builtin_has_a_usable_spec() {
+        for command in crate::commands::impls::builtins() {
+            let Some(spec) = command.args() else { continue };
+            assert!(spec.usage(command.name()).starts_with(&format!("usage: {}", command.name())));
+            // nothing is described twice.
+            let described = spec.describe();
+            let lines: Vec<&str> = described.lines().map(str::trim).collect();
+            let unique: std::collections::HashSet<&str> = lines.iter().copied().collect();
+            assert_eq!(lines.len(), unique.len(), "{}", command.name());
+        }
+    }
+}
@@ -1,9 +1,12 @@
-use std::{cell::Ref, path::Path};
-use crate::core::settings::{Color, WithConsoleColor};
-use crate::{commands::Cmd, core::Terminal};
-use super::{UniError, AsStr};
+use std::{io::{ErrorKind, Write}, path::{Path, PathBuf}, sync::Arc, time::{Duration, Instant}};
+use crate::core::settings::{Color, EditMode, HighlightColors, ReportStatus, Settings};
+use crate::commands::{Cmd, Context};
+use crate::core::completion::Completion;
+use crate::core::{input::HistoryEntry, signals, suggest};
+use crate::error::{CdError, UniError};
+use super::{Outcome, Precedence, plugin::{Plugins, PLUGIN_EXTENSION}};
 use is_executable::IsExecutable;
-use super::args::ArgInfo;
+use super::{args::{self, ArgSpec, Args, Kind}, io::IoContext};
 use termsize::Size;
 
 //macro_rules! println_if {
@@ -14,6 +17,26 @@ use termsize::Size;
 //    }};
 //}
 
+/// every builtin command, in the order they're registered.
+pub fn builtins() -> Vec<Box<dyn Cmd>> {
+    vec![
+        Box::new(HelpCommand),
+        Box::new(HistoryCommand),
+        Box::new(CdCommand),
+        Box::new(LsCommand),
+        Box::new(ManCommand),
+        Box::new(ExitCommand),
+        Box::new(RmDirCommand),
+        Box::new(MkDirCommand),
+        Box::new(ConfigCommand),
+        Box::new(ClearCommand),
+        Box::new(WhereCommand),
+        Box::new(PwdCommand),
+        Box::new(RehashCommand),
+        Box::new(PluginCommand),
+    ]
+}
+
 pub struct HelpCommand;
 
 impl Cmd for HelpCommand {
@@ -26,42 +49,60 @@ impl Cmd for HelpCommand {
 
     fn docs(&self) -> Option<&str> {
         Some("
-        get a list of all commands that exist.
+        get a list of all commands that exist. without -b every
+        file found in the system path will also be displayed.
 
-        flags:
-          -b: this will cause it to only display builtin commands.
-              without this every file found in the system path will
-              also be displayed.
+        given a command, show how to use it instead.
         ")
     }
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, args: Vec<&str>) -> Result<(), Box<dyn super::AsStr>> {
-        let info = ArgInfo::new(&args);
-        let show_only_builtins = info.has_flag('b');
-        for command in ctx.commands().iter() {
-            let b = command.borrow();
-            let desc = match b.desc() {
-                Some(d) => d,
-                None => "No description"
-            };
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new()
+            .flag("-b", "only display builtin commands")
+            .optional("command", Kind::Command, "show how to use this command"))
+    }
+
+    fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let args = args::parse(self, &args)?;
+
+        if let Some(name) = args.value("command") {
+            let command = find_command(ctx, name)?;
+            writeln!(io.stdout, "{} - {}", command.name(), command.desc().unwrap_or("No description"))?;
+            if let Some(spec) = command.args() {
+                writeln!(io.stdout, "{}", spec.usage(command.name()))?;
+            }
+            return Ok(Outcome::success());
+        }
+
+        let show_only_builtins = args.has("-b");
+        for b in ctx.commands.iter() {
+            let desc = b.desc().unwrap_or("No description");
             
             if show_only_builtins {
                 if b.is_builtin() {
-                    println!("{} - {}", b.name(), desc);
+                    writeln!(io.stdout, "{} - {}", b.name(), desc)?;
                 }
             }
             else {
-                println!("{} - {}", b.name(), desc);
+                writeln!(io.stdout, "{} - {}", b.name(), desc)?;
             }
         }
 
-        Ok(())
+        Ok(Outcome::success())
     }
 }
 
 /// Allow the user to view their input history.
 pub struct HistoryCommand;
 
+fn format_duration(duration: Option<Duration>) -> String {
+    match duration {
+        None => "-".to_string(),
+        Some(d) if d.as_secs() >= 1 => format!("{:.2}s", d.as_secs_f64()),
+        Some(d) => format!("{}ms", d.as_millis()),
+    }
+}
+
 impl Cmd for HistoryCommand {
     fn desc(&self) -> Option<&str> {
         Some("view your command history")
@@ -75,39 +116,102 @@ impl Cmd for HistoryCommand {
         Some("
         view your command history. this includes things
         you've entered that aren't commands.
+
+        each entry shows its number (for use with \"!n\"), when it
+        was entered, its exit status and how long it took. --json
+        also includes the directory it was entered in.
         ")
     }
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, _args: Vec<&str>) -> Result<(), Box<dyn super::AsStr>> {
-        for elem in &*ctx.input().history() {
-            eprintln!("{}", elem);
-        }
-        Ok(())
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new()
+            .option("-n", "N", Kind::Count, "only show the last N entries")
+            .option("--grep", "PATTERN", Kind::Text, "only show entries containing PATTERN")
+            .flag("--here", "only show entries entered in this directory")
+            .flag("--failed", "only show entries that exited with a non-zero status")
+            .flag("--json", "print the entries as json")
+            .flag("-c", "clear the history")
+            .option("-d", "N", Kind::Count, "delete entry number N"))
     }
-}
 
-pub struct CdCommand;
+    fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let args = args::parse(self, &args)?;
 
-pub enum CdError {
-    FailedToSetPath(String),
-}
+        if args.has("-c") {
+            ctx.session.history.entries_mut().clear();
+            return Ok(Outcome::success());
+        }
 
-impl AsStr for CdError {
-    fn as_str(&self) -> String {
-        match self {
-            Self::FailedToSetPath(msg) => {
-                format!("failed to set working directory: {}", msg)
+        if let Some(n) = args.number::<usize>("-d") {
+            let history = ctx.session.history.entries_mut();
+            if n == 0 || n > history.len() {
+                return Err(UniError::NotFound(format!("there is no history entry {}.", n)));
             }
+            history.remove(n - 1);
+            return Ok(Outcome::success());
         }
-    }
-}
 
-impl CdError {
-    pub fn boxed(self) -> Box<dyn AsStr> {
-        Box::new(self)
+        let history = ctx.session.history.entries();
+        let cwd = io.cwd.clone();
+        let (here, failed, grep) = (args.has("--here"), args.has("--failed"), args.value("--grep"));
+
+        // keep the original numbers so they still work with "!n"
+        let mut entries: Vec<(usize, &HistoryEntry)> = history
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (i + 1, entry))
+            .filter(|(_, entry)| !here || entry.cwd == *cwd)
+            .filter(|(_, entry)| !failed || entry.status.is_some_and(|s| s != 0))
+            .filter(|(_, entry)| match grep {
+                Some(pattern) => entry.line.contains(pattern),
+                None => true,
+            })
+            .collect();
+
+        if let Some(n) = args.number::<usize>("-n") {
+            let skip = entries.len().saturating_sub(n);
+            entries.drain(..skip);
+        }
+
+        if args.has("--json") {
+            let values: Vec<serde_json::Value> = entries
+                .iter()
+                .map(|(number, entry)| serde_json::json!({
+                    "number": number,
+                    "line": entry.line,
+                    "cwd": entry.cwd,
+                    "timestamp": entry.timestamp.to_rfc3339(),
+                    "status": entry.status,
+                    "duration_ms": entry.duration.map(|d| d.as_millis() as u64),
+                }))
+                .collect();
+
+            match serde_json::to_string_pretty(&values) {
+                Ok(json) => writeln!(io.stdout, "{}", json)?,
+                Err(e) => return Err(UniError::Custom(format!("failed to serialize history: {}", e))),
+            }
+            return Ok(Outcome::success());
+        }
+
+        for (number, entry) in entries {
+            let status = match entry.status {
+                Some(status) => status.to_string(),
+                None => "-".to_string(),
+            };
+            writeln!(io.stdout, "{:>5}  {}  {:>3}  {:>8}  {}",
+                number,
+                entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                status,
+                format_duration(entry.duration),
+                entry.line)?;
+        }
+
+        Ok(Outcome::success())
     }
 }
 
+pub struct CdCommand;
+
 impl Cmd for CdCommand {
     fn name(&self) -> &str {
         "cd"
@@ -126,35 +230,38 @@ impl Cmd for CdCommand {
         ")
     }
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, args: Vec<&str>) -> Result<(), Box<dyn super::AsStr>> {
-        // rusts std::env::set_current_dir() function handles stuff like
-        // "../" etc... (or the native functions do)
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new().arg("dir", Kind::Dir, "the directory to go to"))
+    }
 
-        if args.len() < 1 {
-            return Err(
-                UniError::TooFewArguments(
-                    format!("expected at least one argument, got zero.")
-                ).boxed()
-            )
-        }
+    fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let args = args::parse(self, &args)?;
+        let arg = args.value("dir").unwrap_or_default();
 
-        let mut current_path = ctx.settings().get_path();
-        let arg = args[0];
-        let combined_path = format!("{}/{}", *current_path, arg);
+        // an absolute path replaces the working directory, anything else
+        // is relative to it. only the session moves, not the process.
+        let resolved = std::fs::canonicalize(Path::new(&io.cwd).join(arg)).and_then(|dir| {
+            if dir.is_dir() {
+                Ok(dir)
+            }
+            else {
+                Err(std::io::Error::from(ErrorKind::NotADirectory))
+            }
+        });
 
-        let path = std::path::Path::new(&combined_path);
-        match std::env::set_current_dir(path) {
-            Ok(_) => {
-                // set the path in the prompt
-                // fuck me
-                *current_path = std::env::current_dir().unwrap().as_path().as_os_str().to_str().unwrap().to_string();
-                Ok(())
+        match resolved {
+            Ok(dir) => {
+                ctx.session.set_cwd(dir.to_string_lossy().into_owned());
+                Ok(Outcome::success())
             },
             Err(e) => {
+                let suggestions = match e.kind() {
+                    ErrorKind::NotFound => suggest::closest_dirs(&io.cwd, arg),
+                    _ => Vec::new(),
+                };
                 Err(
-                    CdError::FailedToSetPath(
-                        e.to_string()
-                    ).boxed()
+                    UniError::from(CdError::FailedToSetPath(arg.to_string(), e))
+                        .with_hint(suggest::did_you_mean(&suggestions))
                 )
             }
         }
@@ -174,79 +281,148 @@ impl Cmd for LsCommand {
 
     fn docs(&self) -> Option<&str> {
         Some("
-        list items in the current directory.
+        list items in the current directory, or in the directory given.
 
-        flags:
-          -f: enable filtering
-            -X: filter out executable files
-            -D: filter out directorys
-            -F: filter out regular files
+        -X, -D and -F only do anything along with -f. -R can take a
+        while, Ctrl-C stops it.
         ")
     }
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, args: Vec<&str>) -> Result<(), Box<dyn AsStr>> {
-        let info = ArgInfo::new(&args);
-        let working_directory = ctx.current_path();
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new()
+            .flag("-f", "enable filtering")
+            .flag("-X", "filter out executable files")
+            .flag("-D", "filter out directorys")
+            .flag("-F", "filter out regular files")
+            .flag("-R", "list every subdirectory as well")
+            .optional("dir", Kind::Dir, "the directory to list"))
+    }
+
+    fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let settings = &ctx.session.settings;
+        let info = args::parse(self, &args)?;
+        let working_directory = match info.value("dir") {
+            Some(dir) => Path::new(&io.cwd).join(dir).to_string_lossy().to_string(),
+            None => io.cwd.clone(),
+        };
+
+        if !Path::new(&working_directory).is_dir() {
+            let wanted = info.value("dir").unwrap_or_default();
+            let suggestions = suggest::closest_dirs(&io.cwd, wanted);
+            return Err(UniError::NotFound(
+                format!("there is no directory \"{}\".", wanted)
+            ).with_hint(suggest::did_you_mean(&suggestions)));
+        }
+
+        write!(io.stdout, "* {} ", settings.paint("Executable", &Color::light_green()))?;
+        write!(io.stdout, "* {} ", settings.paint("Directory", &Color::light_blue()))?;
+        write!(io.stdout, "* {}", settings.paint("File", &Color::light_red()))?;
+        writeln!(io.stdout)?;
+
+        if !info.has("-R") {
+            let (items, _) = self.read_items(settings, Path::new(&*working_directory), &info)?;
+            self.print_items(io, items)?;
+            return Ok(Outcome::success());
+        }
+
+        // depth first, like `ls -R`. this can take a while so Ctrl-C is
+        // checked as we go.
+        let mut pending = vec![PathBuf::from(&*working_directory)];
+        while let Some(dir) = pending.pop() {
+            signals::check_interrupted()?;
+
+            let (items, mut subdirs) = match self.read_items(settings, &dir, &info) {
+                Ok(result) => result,
+                Err(e) => {
+                    writeln!(io.stderr, "{}: {}", dir.display(), e)?;
+                    continue;
+                }
+            };
+
+            writeln!(io.stdout, "\n{}:", dir.display())?;
+            self.print_items(io, items)?;
+
+            subdirs.sort();
+            pending.extend(subdirs.into_iter().rev());
+        }
+
+        Ok(Outcome::success())
+    }
+}
+
+/// (name, colored name) pairs, ready to be printed.
+type LsItems = Vec<(String, String)>;
 
-        let iterator = match std::fs::read_dir(&*working_directory) {
+impl LsCommand {
+    /// everything in `dir`, along with the directories in it.
+    fn read_items(&self, settings: &Settings, dir: &Path, info: &Args) -> Result<(LsItems, Vec<PathBuf>), UniError> {
+        let iterator = match std::fs::read_dir(dir) {
             Ok(o) => o,
             Err(e) => {
                 return Err(
-                    UniError::IoError(e).boxed()
+                    UniError::IoError(e)
                 )
             }
         };
 
         let mut items = vec![];
+        let mut subdirs = vec![];
 
-        let has_filter = info.has_flag('f');
+        let has_filter = info.has("-f");
 
         for entry in iterator {
+            signals::check_interrupted()?;
+
             let entry = match entry {
                 Ok(inode) => inode,
-                Err(e) => return Err(UniError::IoError(e).boxed())
+                Err(e) => return Err(UniError::IoError(e))
             };
             let path = entry.path();
 
             let os_name = entry.file_name();
             let name = os_name.to_str().unwrap().to_string();
-            const FILTERED_ITEM: &'static str = "(*)";
+            const FILTERED_ITEM: &str = "(*)";
 
             if path.is_executable() {
-                if info.has_flag('X') && has_filter {
-                    items.push((FILTERED_ITEM.to_string(), format!("(*)")));
+                if info.has("-X") && has_filter {
+                    items.push((FILTERED_ITEM.to_string(), "(*)".to_string()));
                 }
                 else {
-                    items.push((name.clone(), format!("{}", name).rgb(&Color::light_green())));
+                    items.push((name.clone(), settings.paint(&name, &Color::light_green())));
                 }
             }
 
             if path.is_dir() {
-                if info.has_flag('D') && has_filter {
-                    items.push((FILTERED_ITEM.to_string(), format!("(*)")));
+                // don't follow links, they can loop.
+                if !path.is_symlink() {
+                    subdirs.push(path.clone());
+                }
+
+                if info.has("-D") && has_filter {
+                    items.push((FILTERED_ITEM.to_string(), "(*)".to_string()));
                 }
                 else {
-                    items.push((name.clone(), format!("{}", name).rgb(&Color::light_blue())));
+                    items.push((name.clone(), settings.paint(&name, &Color::light_blue())));
                 }
             }
 
             if path.is_file() {
-                if info.has_flag('F') && has_filter {
-                    items.push((FILTERED_ITEM.to_string(), format!("(*)")));
+                if info.has("-F") && has_filter {
+                    items.push((FILTERED_ITEM.to_string(), "(*)".to_string()));
                 }
                 else {
-                    items.push((name.clone(), format!("{}", name).rgb(&Color::light_red())));
+                    items.push((name.clone(), settings.paint(&name, &Color::light_red())));
                 }
             }
         }
 
-        print!("* {} ", "Executable".to_string().rgb(&Color::light_green()));
-        print!("* {} ", "Directory".to_string().rgb(&Color::light_blue()));
-        print!("* {}", "File".to_string().rgb(&Color::light_red()));
-        println!();
+        Ok((items, subdirs))
+    }
 
-        let Size { cols: _, mut rows } = termsize::get().unwrap();
-        rows = rows * 6;
+    fn print_items(&self, io: &mut IoContext<'_>, items: LsItems) -> std::io::Result<()> {
+        // there's no size when we aren't writing to a terminal.
+        let Size { cols: _, mut rows } = termsize::get().unwrap_or(Size { rows: 24, cols: 80 });
+        rows *= 6;
 
         let mut total = 0usize;
 
@@ -254,15 +430,14 @@ impl Cmd for LsCommand {
             total += original.len();
 
             if total > rows as usize {
-                println!();
+                writeln!(io.stdout)?;
                 total = 0;
             }
 
-            print!("{}   ", item);
+            write!(io.stdout, "{}   ", item)?;
         }
 
-        println!();
-        Ok(())
+        writeln!(io.stdout)
     }
 }
 
@@ -280,45 +455,42 @@ impl Cmd for ManCommand {
     fn docs(&self) -> Option<&str> {
         Some("
         view documentation about certain commands.
-
-        usage: 
-          docs <command>
         ")
     }
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, args: Vec<&str>) -> Result<(), Box<dyn AsStr>> {
-        if args.len() < 1 {
-            return Err(
-                UniError::TooFewArguments(
-                    format!("expected the name of a command to show the docs of.")
-                ).boxed()
-            )
-        }
-
-        let name = args[0];
-        let commands = ctx.commands();
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new().arg("command", Kind::Command, "the command to show the docs of"))
+    }
 
-        let command = match commands.get(name) {
-            Some(c) => c,
-            None => {
-                return Err(
-                    UniError::NotFound(
-                        format!("the command \"{}\" does not exist.", name)
-                    ).boxed()
-                )
+    fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let args = args::parse(self, &args)?;
+        let name = args.value("command").unwrap_or_default();
+        let command = find_command(ctx, name)?;
+
+        writeln!(io.stdout, "( documentation for {} )", name)?;
+        if let Some(spec) = command.args() {
+            // indented to line up with the docs.
+            writeln!(io.stdout, "\n        {}\n", spec.usage(name))?;
+            for line in spec.describe().lines() {
+                writeln!(io.stdout, "        {}", line)?;
             }
-        };
+        }
+        let docs = command.docs().unwrap_or("this command has no documentation.");
+        writeln!(io.stdout, "{}", docs)?;
 
-        println!("( documentation for {} )", name);
-        let docs = match command.docs() {
-            Some(s) => s,
-            None => {
-                "this command has no documentation."
-            }
-        };
-        println!("{}", docs);
+        Ok(Outcome::success())
+    }
+}
 
-        Ok(())
+/// look up a command for `man` and `help`.
+fn find_command(ctx: &Context<'_>, name: &str) -> Result<Arc<dyn Cmd>, UniError> {
+    match ctx.commands.get(name) {
+        Some(command) => Ok(command),
+        None => Err(
+            UniError::NotFound(
+                format!("the command \"{}\" does not exist.", name)
+            )
+        ),
     }
 }
 
@@ -336,13 +508,31 @@ impl Cmd for ExitCommand {
     fn docs(&self) -> Option<&str> {
         Some("
         quits the application after saving all configuration.
+        without a status it exits with the status of the last command.
+
+        pressing Ctrl-D on an empty line does the same as \"exit\".
         ")
     }
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, _args: Vec<&str>) -> Result<(), Box<dyn AsStr>> {
-        ctx.quit();
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new().optional("status", Kind::Int, "the status to exit with"))
+    }
 
-        Ok(())
+    fn execute(&self, ctx: &mut Context<'_>, _io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let args = args::parse(self, &args)?;
+        match args.value("status") {
+            Some(status) => match status.parse::<i32>() {
+                Ok(status) => ctx.session.quit_with(status),
+                Err(e) => {
+                    return Err(UniError::Custom(
+                        format!("could not parse \"{}\" as an exit status. ({})", status, e)
+                    ));
+                }
+            },
+            None => ctx.session.quit(),
+        }
+
+        Ok(Outcome::success())
     }
 }
 
@@ -359,30 +549,22 @@ impl Cmd for RmDirCommand {
 
     fn docs(&self) -> Option<&str> {
         Some("
-        remove a directory
-
-        flags:
-          -f: remove all of the folders child files/folders too.
-          \"rmdir <folder> -f\"
-
-        NOTE: all flags must proceed the actual arguments.
+        remove a directory, it has to be empty unless -f is given.
         ")
     }
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, args: Vec<&str>) -> Result<(), Box<dyn AsStr>> {
-        if args.len() < 1 {
-            return Err(
-                UniError::TooFewArguments(
-                    format!("expected at least one argument: <dir_name>")
-                ).boxed()
-            )
-        }
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new()
+            .flag("-f", "remove all of the folders child files/folders too")
+            .arg("dir", Kind::Dir, "the directory to remove"))
+    }
 
-        let info = ArgInfo::new(&args);
-        let remove_all_files_too = info.has_flag('f');
-        let cwd = ctx.current_path();
-        let requested_folder = args[0].to_string();
-        let concated_path = format!("{}/{}", *cwd, requested_folder);
+    fn execute(&self, _ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let args = args::parse(self, &args)?;
+        let remove_all_files_too = args.has("-f");
+        let cwd = &io.cwd;
+        let requested_folder = args.value("dir").unwrap_or_default().to_string();
+        let concated_path = format!("{}/{}", cwd, requested_folder);
 
         let path = if Path::new(&requested_folder).exists() {
             Path::new(&requested_folder)
@@ -396,7 +578,8 @@ impl Cmd for RmDirCommand {
             match std::fs::remove_dir_all(path) {
                 Ok(_) => (),
                 Err(e) => {
-                    eprintln!("failed to remove folder and its contents: ({})", e.to_string());
+                    writeln!(io.stderr, "failed to remove folder and its contents: ({})", e)?;
+                    return Ok(Outcome::exited(1));
                 }
             }
         }
@@ -404,12 +587,13 @@ impl Cmd for RmDirCommand {
             match std::fs::remove_dir(path) {
                 Ok(()) => {},
                 Err(e) => {
-                    eprintln!("failed to remove folder: ({})", e.to_string());
+                    writeln!(io.stderr, "failed to remove folder: ({})", e)?;
+                    return Ok(Outcome::exited(1));
                 }
             }
         }
 
-        Ok(())
+        Ok(Outcome::success())
     }
 }
 
@@ -427,24 +611,18 @@ impl Cmd for MkDirCommand {
     fn docs(&self) -> Option<&str> {
         Some("
         create a directory.
-
-        usage: mkdir <folder_name>
         ")
     }
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, args: Vec<&str>) -> Result<(), Box<dyn AsStr>> {
-        if args.len() < 1 {
-            return Err(
-                UniError::TooFewArguments(
-                    format!("expected at least a directory name argument.")
-                ).boxed()
-            )
-        }
+    fn args(&self) -> Option<ArgSpec> {
+        // only directorys complete, so you can create things inside of them.
+        Some(ArgSpec::new().arg("folder_name", Kind::Dir, "the directory to create"))
+    }
 
-        // this .expect() will never happen ^
-        let path = args.first().expect("there is no path!").to_string();
-        let cwd = ctx.current_path();
-        let full_path = format!("{}/{}", *cwd, path);
+    fn execute(&self, _ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let args = args::parse(self, &args)?;
+        let path = args.value("folder_name").unwrap_or_default().to_string();
+        let full_path = format!("{}/{}", io.cwd, path);
         let tmp_path = Path::new(&path);
 
         let dir_to_create = if tmp_path.is_absolute() {
@@ -457,39 +635,128 @@ impl Cmd for MkDirCommand {
         match std::fs::create_dir(dir_to_create) {
             Ok(()) => (),
             Err(e) => {
-                eprintln!("failed to create directory: {}", e.to_string())
+                writeln!(io.stderr, "failed to create directory: {}", e)?;
+                return Ok(Outcome::exited(1));
             }
         };
 
-        Ok(())
+        Ok(Outcome::success())
     }
 }
 
 pub struct ConfigCommand;
 
-const FLAG_COLOR: char = 'c';
-
-const FLAG_PATH: char = 'P';
-const FLAG_USERNAME: char = 'U';
-const FLAG_BRANCH: char = 'G';
+const FLAG_COLOR: &str = "-c";
+const FLAG_HISTORY_EXPANSION: &str = "-E";
+const FLAG_EDIT_MODE: &str = "-M";
+const FLAG_IGNORE_EOF: &str = "-I";
+const FLAG_PATH_EXTENSIONS: &str = "-X";
+const FLAG_REPORT_STATUS: &str = "-R";
+const FLAG_AUTOCORRECT: &str = "-Y";
+const FLAG_NOT_FOUND_HANDLER: &str = "-W";
+
+const FLAG_PATH: &str = "-P";
+const FLAG_USERNAME: &str = "-U";
+const FLAG_BRANCH: &str = "-G";
+
+const FLAG_HL_COMMAND: &str = "-C";
+const FLAG_HL_UNKNOWN: &str = "-N";
+const FLAG_HL_ARGUMENT: &str = "-A";
+const FLAG_HL_STRING: &str = "-S";
+const FLAG_HL_FLAG: &str = "-F";
+const FLAG_HL_VARIABLE: &str = "-V";
+const FLAG_HL_OPERATOR: &str = "-O";
+const FLAG_HL_SUGGESTION: &str = "-H";
+
+const FLAG_ERROR: &str = "-K";
+const FLAG_HINT: &str = "-T";
+
+const CONFIG_FLAGS: &[(&str, &str)] = &[
+    (FLAG_COLOR, "you're setting a color"),
+    (FLAG_HISTORY_EXPANSION, "turn history expansion on or off"),
+    (FLAG_EDIT_MODE, "switch between emacs and vi key bindings"),
+    (FLAG_IGNORE_EOF, "how many Ctrl-D's it takes to exit"),
+    (FLAG_PATH_EXTENSIONS, "extensions that can be left off of commands"),
+    (FLAG_REPORT_STATUS, "when to say how a command exited"),
+    (FLAG_AUTOCORRECT, "offer to run the closest command when one doesn't exist"),
+    (FLAG_NOT_FOUND_HANDLER, "what to run when a command doesn't exist"),
+    (FLAG_PATH, "the color of the path"),
+    (FLAG_USERNAME, "the color of your username"),
+    (FLAG_BRANCH, "the color of the git branch"),
+    (FLAG_HL_COMMAND, "the color of commands that exist"),
+    (FLAG_HL_UNKNOWN, "the color of commands that don't exist"),
+    (FLAG_HL_ARGUMENT, "the color of plain arguments"),
+    (FLAG_HL_STRING, "the color of quoted strings"),
+    (FLAG_HL_FLAG, "the color of flags"),
+    (FLAG_HL_VARIABLE, "the color of variables"),
+    (FLAG_HL_OPERATOR, "the color of operators"),
+    (FLAG_HL_SUGGESTION, "the color of suggestions from your history"),
+    (FLAG_ERROR, "the color of errors"),
+    (FLAG_HINT, "the color of the hints under errors"),
+];
 
 impl ConfigCommand {
-    fn parse_color_argument(&self, arg: Option<&&str>) -> Result<u8, Box<dyn AsStr>> {
+    fn parse_switch_argument(&self, arg: Option<&&str>) -> Result<bool, UniError> {
+        match arg {
+            Some(&"on") | Some(&"true") => Ok(true),
+            Some(&"off") | Some(&"false") => Ok(false),
+            Some(other) => Err(UniError::Custom(
+                format!("expected \"on\" or \"off\", got \"{}\"", other)
+            )),
+            None => Err(UniError::Custom(
+                "invalid argument format, expected \"on\" or \"off\" but got nothing.".to_string()
+            )),
+        }
+    }
+
+    /// the highlight color picked by the value flags, if any.
+    fn highlight_color<'a>(&self, colors: &'a mut HighlightColors, info: &Args) -> Option<&'a mut Color> {
+        let col = if info.has(FLAG_HL_COMMAND) {
+            &mut colors.command
+        }
+        else if info.has(FLAG_HL_UNKNOWN) {
+            &mut colors.unknown_command
+        }
+        else if info.has(FLAG_HL_ARGUMENT) {
+            &mut colors.argument
+        }
+        else if info.has(FLAG_HL_STRING) {
+            &mut colors.string
+        }
+        else if info.has(FLAG_HL_FLAG) {
+            &mut colors.flag
+        }
+        else if info.has(FLAG_HL_VARIABLE) {
+            &mut colors.variable
+        }
+        else if info.has(FLAG_HL_OPERATOR) {
+            &mut colors.operator
+        }
+        else if info.has(FLAG_HL_SUGGESTION) {
+            &mut colors.suggestion
+        }
+        else {
+            return None;
+        };
+        Some(col)
+    }
+
+    fn parse_color_argument(&self, arg: Option<&&str>) -> Result<u8, UniError> {
         if let Some(actual) = arg {
-            return match actual.parse::<u8>() {
+            match actual.parse::<u8>() {
                 Ok(val) => Ok(val),
                 Err(e) => {
                     Err(UniError::Custom(
-                        format!("could not parse \"{}\" as u8. ({})", actual, e.to_string())
-                    ).boxed())
+                        format!("could not parse \"{}\" as u8. ({})", actual, e)
+                    ))
                 }
             }
         }
         else {
             Err(
                 UniError::Custom(
-                    format!("invalid argument format, expected value for color but got nothing.")
-                ).boxed()
+                    "invalid argument format, expected value for color but got nothing.".to_string()
+                )
             )
         }
     }
@@ -508,12 +775,22 @@ impl Cmd for ConfigCommand {
         Some("
         configure your term-rs experience.
 
-        flags:
-          -c: This flag tells use you're setting a color config value.
-        values:
-          -P: You're setting the paths color.
-          -U: You're setting the color of your username in the prompt.
-          -G: You're setting the color of the github branch (when applicable)
+        -c along with one of -P, -U, -G, -C, -N, -A, -S, -F, -V, -O,
+        -H, -K or -T sets that color, the values are its red, green and
+        blue.
+        the others take a single value:
+          \"cfg off -E\"
+          \"cfg vi -M\"
+          \"cfg 3 -I\"
+          \"cfg .exe,.bat -X\" (or \"none\")
+          \"cfg always -R\" (or \"never\", the default is \"failures\")
+          \"cfg on -Y\"
+          \"cfg /usr/local/bin/not-found -W\" (or \"none\")
+
+        the -W handler runs whenever a command doesn't exist, with the
+        command's name and arguments as its arguments. it can run a
+        fallback or suggest a package to install. when it exits with 0
+        that's the result, otherwise the usual error is shown.
 
         example:
                R  G B flags
@@ -526,49 +803,153 @@ impl Cmd for ConfigCommand {
         ")
     }
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, args: Vec<&str>) -> Result<(), Box<dyn AsStr>> {
-        if args.len() < 1 {
+    fn args(&self) -> Option<ArgSpec> {
+        let spec = CONFIG_FLAGS
+            .iter()
+            .fold(ArgSpec::new(), |spec, (flag, help)| spec.flag(flag, help));
+        Some(spec.repeated("value", Kind::Text, "what to set it to"))
+    }
+
+    fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let info = args::parse(self, &args)?;
+        let args = info.values("value");
+        if args.is_empty() {
             return Err(
                 UniError::TooFewArguments(
                     format!("{} expects at least one argument.", self.name())
-                ).boxed()
+                )
             )
         }
 
-        let info = ArgInfo::new(&args);
-
-        if info.has_flag(FLAG_COLOR) {
-            let mut col = if info.has_flag(FLAG_PATH) {
-                ctx.settings().get_path_color()
+        if info.has(FLAG_COLOR) {
+            let settings = &mut ctx.session.settings;
+            let col = if info.has(FLAG_PATH) {
+                &mut settings.path_color
             }
-            else if info.has_flag(FLAG_USERNAME) {
-                ctx.settings().get_username_color()
+            else if info.has(FLAG_USERNAME) {
+                &mut settings.user_name_color
             }
-            else if info.has_flag(FLAG_BRANCH) {
-                ctx.settings().get_branch_color()
+            else if info.has(FLAG_BRANCH) {
+                &mut settings.git_branch_color
+            }
+            else if info.has(FLAG_ERROR) {
+                &mut settings.errors.error
+            }
+            else if info.has(FLAG_HINT) {
+                &mut settings.errors.hint
+            }
+            else if let Some(col) = self.highlight_color(&mut settings.highlight, &info) {
+                col
             }
             else {
                 return Err(
                     UniError::Custom(
                         format!("no recognized color flag. (use \"man {}\")", self.name())
-                    ).boxed()
+                    )
                 );
             };
 
             // we expect the arguments to be formatted like this:
             // args[0] = r, args[1] = g, args[2] = b
-            let r = self.parse_color_argument(args.get(0))?;
+            let r = self.parse_color_argument(args.first())?;
             let g = self.parse_color_argument(args.get(1))?;
             let b = self.parse_color_argument(args.get(2))?;
 
             let color = Color::new(r, g, b);
             *col = color;
 
-            return Ok(());
+            return Ok(Outcome::success());
+        }
+
+        if info.has(FLAG_EDIT_MODE) {
+            let mode = match args.first() {
+                Some(&"emacs") => EditMode::Emacs,
+                Some(&"vi") => EditMode::Vi,
+                _ => {
+                    return Err(UniError::Custom(
+                        "expected the edit mode to be \"emacs\" or \"vi\".".to_string()
+                    ));
+                }
+            };
+            ctx.session.settings.edit_mode = mode;
+            return Ok(Outcome::success());
         }
 
-        eprintln!("no recognized flags, no work to do.");
-        Ok(())
+        if info.has(FLAG_IGNORE_EOF) {
+            let count = match args.first().map(|arg| arg.parse::<u32>()) {
+                Some(Ok(count)) => count,
+                _ => {
+                    return Err(UniError::Custom(
+                        "expected the number of Ctrl-D's needed to exit.".to_string()
+                    ));
+                }
+            };
+            ctx.session.settings.ignore_eof = count;
+            return Ok(Outcome::success());
+        }
+
+        if info.has(FLAG_PATH_EXTENSIONS) {
+            let extensions: Vec<String> = match args.first() {
+                Some(&"none") => Vec::new(),
+                Some(list) => list
+                    .split(',')
+                    .filter(|ext| !ext.is_empty())
+                    .map(|ext| ext.to_string())
+                    .collect(),
+                None => {
+                    return Err(UniError::Custom(
+                        "expected a comma separated list of extensions.".to_string()
+                    ));
+                }
+            };
+            ctx.session.settings.path_extensions = extensions.clone();
+            ctx.commands.set_path_extensions(extensions);
+            return Ok(Outcome::success());
+        }
+
+        if info.has(FLAG_REPORT_STATUS) {
+            let report = match args.first() {
+                Some(&"never") => ReportStatus::Never,
+                Some(&"failures") => ReportStatus::Failures,
+                Some(&"always") => ReportStatus::Always,
+                _ => {
+                    return Err(UniError::Custom(
+                        "expected \"never\", \"failures\" or \"always\".".to_string()
+                    ));
+                }
+            };
+            ctx.session.settings.report_status = report;
+            return Ok(Outcome::success());
+        }
+
+        if info.has(FLAG_HISTORY_EXPANSION) {
+            let value = self.parse_switch_argument(args.first())?;
+            ctx.session.settings.history_expansion = value;
+            return Ok(Outcome::success());
+        }
+
+        if info.has(FLAG_NOT_FOUND_HANDLER) {
+            let handler = match args.first() {
+                Some(&"none") => None,
+                Some(handler) => Some(handler.to_string()),
+                None => {
+                    return Err(UniError::Custom(
+                        "expected a command to run, or \"none\".".to_string()
+                    ));
+                }
+            };
+            ctx.session.settings.not_found_handler = handler;
+            return Ok(Outcome::success());
+        }
+
+        if info.has(FLAG_AUTOCORRECT) {
+            let value = self.parse_switch_argument(args.first())?;
+            ctx.session.settings.autocorrect = value;
+            return Ok(Outcome::success());
+        }
+
+        writeln!(io.stderr, "no recognized flags, no work to do.")?;
+        Ok(Outcome::exited(1))
     }
 }
 
@@ -591,9 +972,14 @@ impl Cmd for ClearCommand {
         ")
     }
 
-    fn execute(&self, _: Ref<'_, &Terminal>, _: Vec<&str>) -> Result<(), Box<dyn AsStr>> {
-        print!("\x1B[2J");
-        Ok(())
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new())
+    }
+
+    fn execute(&self, _: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        args::parse(self, &args)?;
+        write!(io.stdout, "\x1B[2J")?;
+        Ok(Outcome::success())
     }
 }
 
@@ -612,34 +998,167 @@ impl Cmd for WhereCommand {
         Some("
         find the location of a command.
 
-        usage: where <name>
+        with -a every command with that name is shown, in the order
+        they're picked. functions come first, then builtins, then PATH
+        in order.
         ")
     }
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, args: Vec<&str>) -> Result<(), Box<dyn AsStr>> {
-        if args.len() < 1 {
-            return Err(
-                UniError::TooFewArguments(
-                    format!("{} expects at least one argument.", self.name())
-                ).boxed()
-            )
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new()
+            .flag("-a", "show every match, not just the one that runs")
+            .arg("name", Kind::Command, "the command to find"))
+    }
+
+    fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let info = args::parse(self, &args)?;
+        let name = info.value("name").unwrap_or_default();
+
+        let mut matches = ctx.commands.get_all(name);
+        if matches.is_empty() {
+            writeln!(io.stdout, "no such command \"{}\"", name)?;
+            return Ok(Outcome::exited(1));
         }
 
-        let name = args[0];
-        match ctx.commands().get(name) {
-            Some(cmd) => {
-                let path = match cmd.file_location() {
-                    Some(p) => p,
-                    None => "this command is builtin".to_string()
+        if !info.has("-a") {
+            matches.truncate(1);
+        }
+
+        for (precedence, cmd) in matches {
+            let path = match (precedence, cmd.file_location()) {
+                (Precedence::Function, _) => "this command is a function".to_string(),
+                (_, Some(p)) => p,
+                (_, None) => "this command is builtin".to_string(),
+            };
+            writeln!(io.stdout, "{}: {}", name, path)?;
+        }
+
+        Ok(Outcome::success())
+    }
+}
+
+pub struct RehashCommand;
+
+impl Cmd for RehashCommand {
+    fn name(&self) -> &str {
+        "rehash"
+    }
+
+    fn desc(&self) -> Option<&str> {
+        Some("re-read every directory in PATH")
+    }
+
+    fn docs(&self) -> Option<&str> {
+        Some("
+        forget the commands found in PATH and read every directory again.
+
+        directories in PATH are only read when a command is first looked
+        up, and re-read when they change, so you shouldn't normally need
+        this. it's here for when something slips through.
+        ")
+    }
+
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new())
+    }
+
+    fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        args::parse(self, &args)?;
+        let started = Instant::now();
+        let folders = ctx.commands.rehash();
+        writeln!(io.stdout, "read {} directories ({} commands) in {:.2?}",
+            folders,
+            ctx.commands.count(),
+            started.elapsed())?;
+        Ok(Outcome::success())
+    }
+}
+
+pub struct PluginCommand;
+
+const PLUGIN_ACTIONS: &[(&str, &str)] = &[
+    ("list", "show the loaded plugins"),
+    ("load", "load a plugin"),
+    ("unload", "unload a plugin"),
+];
+
+impl Cmd for PluginCommand {
+    fn name(&self) -> &str {
+        "plugin"
+    }
+
+    fn desc(&self) -> Option<&str> {
+        Some("list, load and unload plugins")
+    }
+
+    fn docs(&self) -> Option<&str> {
+        Some("
+        manage plugins, which add commands. a plugin is either a shared
+        library, or an executable that speaks json-rpc over stdio (see
+        examples/rpc_plugin.rs).
+
+        plugins in ~/.term-rs/plugins are loaded when term-rs starts.
+
+        \"plugin load\" takes the path to a plugin, a plain name is
+        looked for in the plugins folder. \"plugin unload\" takes the
+        name of a loaded plugin and removes its commands.
+        ")
+    }
+
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new()
+            .arg("action", Kind::OneOf(PLUGIN_ACTIONS), "what to do")
+            .optional("plugin", Kind::Path, "the plugin to load or unload"))
+    }
+
+    fn complete(&self, ctx: &Context<'_>, args: &[&str], cursor: usize) -> Option<Vec<Completion>> {
+        match (cursor, args.first()) {
+            (1, Some(&"unload")) => Some(ctx.plugins
+                .loaded()
+                .iter()
+                .map(|plugin| Completion::new(plugin.name.clone()))
+                .collect()),
+            (1, Some(&"list")) => Some(Vec::new()),
+            _ => self.args().map(|spec| spec.complete(ctx, args, cursor)),
+        }
+    }
+
+    fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let args = args::parse(self, &args)?;
+        match (args.value("action"), args.value("plugin")) {
+            (Some("list"), _) => {
+                let loaded = ctx.plugins.loaded();
+                if loaded.is_empty() {
+                    writeln!(io.stdout, "no plugins are loaded.")?;
+                }
+                for plugin in loaded.iter() {
+                    writeln!(io.stdout, "{} {} ({})", plugin.name, plugin.version, plugin.location)?;
+                    writeln!(io.stdout, "  commands: {}", plugin.commands.join(", "))?;
+                }
+                Ok(Outcome::success())
+            },
+            (Some("load"), Some(target)) => {
+                let path = if target.contains('/') {
+                    Path::new(&io.cwd).join(target)
+                }
+                else {
+                    // a plain name is a plugin in the plugins folder, either
+                    // an executable with that name or a library.
+                    let folder = Plugins::folder(&ctx.session.settings).unwrap_or_default();
+                    let mut path = Path::new(&folder).join(target);
+                    if path.extension().is_none() && !(path.is_file() && path.is_executable()) {
+                        path.set_extension(PLUGIN_EXTENSION);
+                    }
+                    path
                 };
-                println!("{}: {}", name, path);
+                ctx.plugins.load(ctx.commands, &path).map(|_| Outcome::success())
             },
-            None => {
-                println!("no such command \"{}\"", name);
-            }
+            (Some("unload"), Some(name)) => ctx.plugins.unload(ctx.commands, name).map(|_| Outcome::success()),
+            (Some(action), _) => Err(
+                UniError::TooFewArguments(format!("plugin {} expects a plugin.", action))
+            ),
+            (None, _) => unreachable!("the action is required"),
         }
-
-        Ok(())
     }
 }
 
@@ -662,26 +1181,31 @@ impl Cmd for PwdCommand {
         std::env::current_dir() and also our current location that
         we track.
 
-        NOTE: They are typically the same location.
+        NOTE: They are typically the same location. A terminal embedded
+              in another program leaves the process where it was.
         ")
     }
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, _args: Vec<&str>) -> Result<(), Box<dyn AsStr>> {
-        let settings = ctx.settings();
-        let our_location = settings.get_path();
+    fn args(&self) -> Option<ArgSpec> {
+        Some(ArgSpec::new())
+    }
+
+    fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        args::parse(self, &args)?;
+        let our_location = ctx.cwd();
 
         let sys_dir = match std::env::current_dir() {
             Ok(buf) => {
                 buf.as_path().as_os_str().to_str().unwrap().to_string()
             },
             Err(e) => {
-                format!("failed to get path via system. ({})", e.to_string())
+                format!("failed to get path via system. ({})", e)
             }
         };
 
-        println!("locally: {}", our_location);
-        println!("sys: {}", sys_dir);
+        writeln!(io.stdout, "locally: {}", our_location)?;
+        writeln!(io.stdout, "sys: {}", sys_dir)?;
 
-        Ok(())
+        Ok(Outcome::success())
     }
 }
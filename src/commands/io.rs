@@ -0,0 +1,123 @@
+// What a command reads from and writes to.
+//
+// Commands get an `IoContext` instead of using the process's stdio
+// directly, so their output can be captured, redirected or piped
+// somewhere else.
+
+use std::{collections::HashMap, ffi::OsString, io::{BufRead, BufReader, Write}};
+
+/// environment variables, kept as the os has them since they don't
+/// have to be utf-8.
+pub type Env = HashMap<OsString, OsString>;
+
+pub struct IoContext<'a> {
+    /// `Send`, so it can be fed to a child from another thread.
+    pub stdin: Box<dyn BufRead + Send + 'a>,
+    pub stdout: Box<dyn Write + 'a>,
+    pub stderr: Box<dyn Write + 'a>,
+    /// the environment the command runs with.
+    pub env: Env,
+    /// the directory the command runs in.
+    pub cwd: String,
+    /// the streams are the shell's own, so child processes can just
+    /// inherit them.
+    inherited: bool,
+    /// only stdin is the shell's own, the output is captured.
+    stdin_inherited: bool,
+}
+
+impl IoContext<'static> {
+    /// the shell's own stdio.
+    pub fn inherit(env: Env, cwd: String) -> IoContext<'static> {
+        IoContext {
+            // not locked, scripts piped into us are read from stdin too.
+            stdin: Box::new(BufReader::new(std::io::stdin())),
+            stdout: Box::new(std::io::stdout()),
+            stderr: Box::new(std::io::stderr()),
+            env,
+            cwd,
+            inherited: true,
+            stdin_inherited: true,
+        }
+    }
+}
+
+impl<'a> IoContext<'a> {
+    /// run with other streams, like a buffer to capture the output.
+    pub fn new(
+        stdin: impl BufRead + Send + 'a,
+        stdout: impl Write + 'a,
+        stderr: impl Write + 'a,
+        env: Env,
+        cwd: String,
+    ) -> IoContext<'a> {
+        IoContext {
+            stdin: Box::new(stdin),
+            stdout: Box::new(stdout),
+            stderr: Box::new(stderr),
+            env,
+            cwd,
+            inherited: false,
+            stdin_inherited: false,
+        }
+    }
+
+    pub fn is_inherited(&self) -> bool {
+        self.inherited
+    }
+
+    /// stdin is the shell's own, even if the output isn't. a child can
+    /// read it directly rather than having it copied.
+    pub fn stdin_is_inherited(&self) -> bool {
+        self.stdin_inherited
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.stdout.flush()?;
+        self.stderr.flush()
+    }
+}
+
+/// the streams a terminal runs its commands with, its own stdio unless
+/// it was given others.
+#[derive(Default)]
+pub enum Streams {
+    #[default]
+    Inherit,
+    Custom {
+        stdin: Box<dyn BufRead + Send>,
+        stdout: Box<dyn Write>,
+        stderr: Box<dyn Write>,
+    },
+}
+
+impl Streams {
+    /// what a command run in `cwd` with `env` gets.
+    pub fn io(&mut self, env: Env, cwd: String) -> IoContext<'_> {
+        match self {
+            Streams::Inherit => IoContext::inherit(env, cwd),
+            Streams::Custom { stdin, stdout, stderr } => {
+                IoContext::new(stdin, stdout, stderr, env, cwd)
+            }
+        }
+    }
+
+    /// what a command gets when its output goes to `stdout` and
+    /// `stderr` instead, it still reads from these streams.
+    pub fn capture<'a>(
+        &'a mut self,
+        stdout: impl Write + 'a,
+        stderr: impl Write + 'a,
+        env: Env,
+        cwd: String,
+    ) -> IoContext<'a> {
+        match self {
+            Streams::Inherit => {
+                let mut io = IoContext::new(BufReader::new(std::io::stdin()), stdout, stderr, env, cwd);
+                io.stdin_inherited = true;
+                io
+            },
+            Streams::Custom { stdin, .. } => IoContext::new(stdin, stdout, stderr, env, cwd),
+        }
+    }
+}
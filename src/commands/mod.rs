@@ -1,121 +1,360 @@
 pub mod impls;
 pub mod args;
+pub mod io;
 pub mod native;
+pub mod plugin;
+pub mod rpc;
 
-use crate::core::Terminal;
+use crate::core::{completion::Completion, lexer, session::Session, suggest};
+use crate::error::UniError;
+use crate::logger::*;
 
-use std::{cell::{Ref, RefCell, RefMut}, io::Error, path::Path};
+use std::{collections::HashMap, io::{BufRead, Write}, path::Path, process::ExitStatus, sync::{Arc, Mutex}, time::{Duration, Instant, SystemTime}};
 
-use self::native::PathLoadedCommand;
+use self::{args::ArgSpec, io::IoContext, native::PathLoadedCommand, plugin::Plugins};
 use is_executable::IsExecutable;
 
-pub trait AsStr {
-    fn as_str(&self) -> String;
+/// commands can be shared between threads, anything they change while
+/// running belongs in the `Context` they're given.
+pub trait Cmd: Send + Sync {
+    fn name(&self) -> &str;
+    // NOTE: these two are optional because commands loaded
+    // from the path dont have descriptions or docs.
+    fn desc(&self) -> Option<&str>;
+    fn docs(&self) -> Option<&str>;
+
+    fn is_builtin(&self) -> bool {
+        true
+    }
+    fn file_location(&self) -> Option<String> {
+        None
+    }
+
+    /// what arguments the command takes. `None` means anything goes,
+    /// like for commands from PATH.
+    fn args(&self) -> Option<ArgSpec> {
+        None
+    }
+
+    /// complete `args[cursor]`, the word the user is typing right now.
+    /// returning `None` means the command has no opinion, and paths are
+    /// completed instead. by default this goes by `args`.
+    fn complete(&self, ctx: &Context<'_>, args: &[&str], cursor: usize) -> Option<Vec<Completion>> {
+        self.args().map(|spec| spec.complete(ctx, args, cursor))
+    }
+
+    /// run the command, reading and writing through `io`. `Err` is for
+    /// when it couldn't run at all, a command that ran and failed returns
+    /// an `Outcome` with a non-zero code.
+    fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError>;
 }
 
-pub enum UniError {
-    NotFound(String),
-    CommandError(Box<dyn AsStr>),
-    TooFewArguments(String),
-    IoError(Error),
-    Custom(String),
+/// what a command can see and change while it runs. it's `Send`, so a
+/// command can be run on another thread.
+pub struct Context<'a> {
+    pub session: &'a mut Session,
+    pub commands: &'a Commands,
+    pub plugins: &'a Plugins,
 }
 
-impl UniError {
-    pub fn boxed(self) -> Box<dyn AsStr> {
-        Box::new(self)
+impl Context<'_> {
+    /// the working directory.
+    pub fn cwd(&self) -> &str {
+        self.session.cwd()
     }
 }
 
-impl AsStr for UniError {
-    fn as_str(&self) -> String {
-        match self {
-            UniError::NotFound(info) => {
-                format!("NotFound: {}", info)
-            },
-            UniError::CommandError(e) => {
-                format!("CommandErr: {}", e.as_str())
-            },
-            UniError::TooFewArguments(msg) => {
-                format!("not enough arguments: {}", msg)
-            },
-            UniError::IoError(e) => {
-                format!("IoError: {}", e.to_string())
-            },
-            UniError::Custom(s) => {
-                format!("{}", s)
-            }
+/// how a command finished.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Outcome {
+    /// the exit code, 0 means it worked.
+    pub code: i32,
+    /// the signal that killed it, if one did. `code` is 128 + the signal.
+    pub signal: Option<i32>,
+    /// how long it ran, filled in by `Commands::execute`.
+    pub duration: Duration,
+}
+
+impl Outcome {
+    pub fn success() -> Outcome {
+        Outcome::default()
+    }
+
+    pub fn exited(code: i32) -> Outcome {
+        Outcome {
+            code,
+            ..Outcome::default()
         }
     }
-}
 
-pub trait Cmd {
-    fn name(&self) -> &str;
-    // NOTE: these two are optional because commands loaded
-    // from the path dont have descriptions or docs.
-    fn desc(&self) -> Option<&str>;
-    fn docs(&self) -> Option<&str>;
+    pub fn signaled(signal: i32) -> Outcome {
+        Outcome {
+            code: 128 + signal,
+            signal: Some(signal),
+            ..Outcome::default()
+        }
+    }
 
-    fn is_builtin(&self) -> bool {
-        true
+    pub fn is_success(&self) -> bool {
+        self.code == 0
     }
-    fn file_location(&self) -> Option<String> {
-        None
+}
+
+impl From<ExitStatus> for Outcome {
+    fn from(status: ExitStatus) -> Outcome {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return Outcome::signaled(signal);
+            }
+        }
+        Outcome::exited(status.code().unwrap_or(1))
     }
+}
+
+/// where a command came from. when two commands share a name the one
+/// that sorts first wins.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Precedence {
+    Function,
+    Builtin,
+    /// from a plugin, they can't replace builtins but do win over PATH.
+    Plugin,
+    /// found in PATH, the index is which PATH directory it was in.
+    Path(usize),
+}
+
+struct Entry {
+    precedence: Precedence,
+    command: Arc<dyn Cmd>,
+}
 
-    fn execute(&self, ctx: Ref<'_, &Terminal>, args: Vec<&str>) -> Result<(), Box<dyn AsStr>>;
+/// a directory from PATH. its commands are only read when they're first
+/// needed, and again whenever the directory changes.
+struct PathFolder {
+    path: String,
+    /// when we last read it, `None` means never.
+    modified: Option<SystemTime>,
 }
 
 pub struct Commands {
-    storage: Vec<RefCell<Box<dyn Cmd>>>
+    /// every command with a given name, best first. the rest are kept
+    /// around so `where -a` can show what's being shadowed.
+    storage: Mutex<HashMap<String, Vec<Entry>>>,
+    path_folders: Mutex<Vec<PathFolder>>,
+    /// executables ending in one of these can also be run without it,
+    /// like PATHEXT on windows.
+    path_extensions: Mutex<Vec<String>>,
 }
 
-type Context<'a> = Ref<'a, &'a Terminal>;
+impl Default for Commands {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Commands {
     pub fn new() -> Commands {
         Commands {
-            storage: Vec::new()
+            storage: Mutex::new(HashMap::new()),
+            path_folders: Mutex::new(Vec::new()),
+            path_extensions: Mutex::new(Vec::new()),
         }
     }
 
-    pub fn push(&mut self, cmd: Box<dyn Cmd>) {
-        self.storage.push(RefCell::new(cmd));
+    /// add a builtin command.
+    pub fn push(&self, cmd: Box<dyn Cmd>) {
+        self.insert(Precedence::Builtin, cmd.into());
+    }
+
+    /// add a command from a plugin.
+    pub fn push_plugin(&self, cmd: Box<dyn Cmd>) {
+        self.insert(Precedence::Plugin, cmd.into());
+    }
+
+    /// add a function, these win over everything else.
+    pub fn push_function(&self, cmd: Box<dyn Cmd>) {
+        self.insert(Precedence::Function, cmd.into());
+    }
+
+    fn insert(&self, precedence: Precedence, command: Arc<dyn Cmd>) {
+        let mut storage = self.storage.lock().unwrap();
+        let entries = storage.entry(command.name().to_string()).or_default();
+        // after anything with the same precedence, so the first one added wins.
+        let index = entries.partition_point(|entry| entry.precedence <= precedence);
+        entries.insert(index, Entry { precedence, command });
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Cmd>> {
+        self.refresh_path();
+        self.storage
+            .lock()
+            .unwrap()
+            .get(name)
+            .and_then(|entries| entries.first())
+            .map(|entry| Arc::clone(&entry.command))
+    }
+
+    /// remove every command that `keep` says no to.
+    pub fn retain(&self, keep: impl Fn(&dyn Cmd) -> bool) {
+        self.storage.lock().unwrap().retain(|_, entries| {
+            entries.retain(|entry| keep(entry.command.as_ref()));
+            !entries.is_empty()
+        });
+    }
+
+    /// every command called `name`, in the order they would be picked.
+    pub fn get_all(&self, name: &str) -> Vec<(Precedence, Arc<dyn Cmd>)> {
+        self.refresh_path();
+        match self.storage.lock().unwrap().get(name) {
+            Some(entries) => entries
+                .iter()
+                .map(|entry| (entry.precedence, Arc::clone(&entry.command)))
+                .collect(),
+            None => Vec::new(),
+        }
     }
 
-    pub fn get(&self, name: &str) -> Option<Ref<'_, Box<dyn Cmd>>> {
-        for elem in &self.storage {
-            let b = elem.borrow();
-            if b.name() == name {
-                return Some(b);
+    pub fn execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, name: &str, args: Vec<&str>) -> Result<Outcome, UniError> {
+        // `./app` and `/usr/bin/env` are run straight from the filesystem.
+        let command: Arc<dyn Cmd> = if native::is_path_command(name) {
+            Arc::new(native::resolve_path_command(&io.cwd, name)?)
+        }
+        else {
+            match self.get(name) {
+                Some(command) => command,
+                None => return self.not_found(ctx, io, name, args),
+            }
+        };
+
+        let report = ctx.session.settings.report_status;
+        let started = Instant::now();
+        let mut outcome = command.execute(ctx, io, args)?;
+        outcome.duration = started.elapsed();
+
+        // builtins explain their own failures.
+        if !command.is_builtin() && report.should_report(&outcome) {
+            match outcome.signal {
+                Some(signal) => writeln!(io.stderr, "{} was killed by signal ({})", name, signal)?,
+                None => writeln!(io.stderr, "{} exited with status ({})", name, outcome.code)?,
             }
         }
-        None
+
+        Ok(outcome)
     }
 
-    pub fn get_mut(&self, name: &str) -> Option<RefMut<'_, Box<dyn Cmd>>> {
-        for elem in &self.storage {
-            let b = elem.borrow();
-            if b.name() == name {
-                return Some(elem.borrow_mut())
+    /// `name` doesn't exist. the handler gets the first go, then we say
+    /// what might have been meant instead. with autocorrect on we offer
+    /// to run the closest one.
+    fn not_found(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, name: &str, args: Vec<&str>) -> Result<Outcome, UniError> {
+        if let Some(outcome) = self.run_not_found_handler(ctx, io, name, &args)? {
+            return Ok(outcome);
+        }
+
+        let suggestions: Vec<String> = {
+            let uses = suggest::command_uses(ctx.session.history.entries());
+            let commands: Vec<Arc<dyn Cmd>> = self.iter().collect();
+            suggest::closest(name, commands.iter().map(|command| command.name()), &uses)
+                .into_iter()
+                .map(String::from)
+                .collect()
+        };
+
+        let ask = ctx.session.settings.autocorrect && ctx.session.is_interactive() && io.is_inherited();
+        if let (Some(best), true) = (suggestions.first(), ask) {
+            write!(io.stderr, "{}: command not found, run `{}` instead? [y/N] ", name, best)?;
+            io.stderr.flush()?;
+
+            let mut answer = String::new();
+            io.stdin.read_line(&mut answer)?;
+            if answer.trim().eq_ignore_ascii_case("y") {
+                return self.execute(ctx, io, best, args);
             }
         }
-        None
+
+        Err(UniError::NotFound(
+            format!("the command {} does not exist.", name)
+        ).with_hint(suggest::did_you_mean(&suggestions)))
     }
 
-    pub fn execute(&self, ctx: Context<'_>, name: &str, args: Vec<&str>) -> Result<(), Box<dyn AsStr>> {
-        if let Some(command) = self.get(name) {
-            command.execute(ctx, args)
+    /// run the user's command-not-found handler as `handler name args..`.
+    /// `None` means there isn't one, or it didn't handle it, so the usual
+    /// error should be shown.
+    fn run_not_found_handler(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, name: &str, args: &[&str]) -> Result<Option<Outcome>, UniError> {
+        let handler = match ctx.session.settings.not_found_handler.clone() {
+            Some(handler) if !handler.is_empty() => handler,
+            _ => return Ok(None),
+        };
+
+        // looked up directly, going through `execute` would end up back
+        // here when the handler itself doesn't exist.
+        let command: Option<Arc<dyn Cmd>> = if native::is_path_command(&handler) {
+            native::resolve_path_command(&io.cwd, &handler)
+                .ok()
+                .map(|command| Arc::new(command) as Arc<dyn Cmd>)
         }
         else {
-            Err(UniError::NotFound(format!("the command {} does not exist.", name)).boxed())
+            self.get(&handler)
+        };
+        let Some(command) = command else {
+            writeln!(io.stderr, "the command-not-found handler \"{}\" doesn't exist.", handler)?;
+            return Ok(None);
+        };
+
+        let mut handler_args = vec![name];
+        handler_args.extend_from_slice(args);
+        match command.execute(ctx, io, handler_args) {
+            Ok(outcome) if outcome.is_success() => Ok(Some(outcome)),
+            Ok(_) => Ok(None),
+            Err(e) => {
+                writeln!(io.stderr, "the command-not-found handler failed: {}", e)?;
+                Ok(None)
+            }
         }
     }
 
-    pub fn add_path_folder(&mut self, path: String) -> std::io::Result<()> {
-        let path = Path::new(&path);
+    /// remember a PATH directory, nothing is read from it until a
+    /// command is looked up. folders added earlier win, just like the
+    /// order of PATH.
+    pub fn add_path_folder(&mut self, path: String) {
+        self.path_folders.get_mut().unwrap().push(PathFolder {
+            path,
+            modified: None,
+        });
+    }
+
+    /// re-read any PATH directory that changed since we last looked.
+    fn refresh_path(&self) {
+        let mut folders = self.path_folders.lock().unwrap();
+
+        for (index, folder) in folders.iter_mut().enumerate() {
+            let modified = match std::fs::metadata(&folder.path).and_then(|m| m.modified()) {
+                Ok(modified) => modified,
+                // it doesn't exist (anymore), make sure nothing is left behind.
+                Err(_) => {
+                    if folder.modified.take().is_some() {
+                        self.remove_path_folder(index);
+                    }
+                    continue;
+                }
+            };
+
+            if folder.modified == Some(modified) {
+                continue;
+            }
+
+            self.remove_path_folder(index);
+            if let Err(e) = self.load_path_folder(index, &folder.path) {
+                log!("failed to read PATH directory {}: {}", folder.path, e);
+            }
+            folder.modified = Some(modified);
+        }
+    }
 
-        for entry in std::fs::read_dir(path)? {
+    fn load_path_folder(&self, index: usize, path: &str) -> std::io::Result<()> {
+        let extensions = self.path_extensions.lock().unwrap().clone();
+        for entry in std::fs::read_dir(Path::new(path))? {
             let entry = entry?;
             let path = entry.path();
 
@@ -125,42 +364,151 @@ impl Commands {
 
             if path.is_executable() {
                 let full_path = path.as_os_str().to_str().unwrap().to_string();
-                let cmd = PathLoadedCommand::new(full_path)?;
-                self.storage.push(RefCell::new(Box::new(cmd)));
+                let cmd = PathLoadedCommand::new(full_path.clone())?;
+
+                // keep both `app.exe` and `app`.
+                let stripped = native::strip_extension(cmd.name(), &extensions)
+                    .map(|name| name.to_string());
+                if let Some(name) = stripped {
+                    let alias = PathLoadedCommand::with_name(full_path, name);
+                    self.insert(Precedence::Path(index), Arc::new(alias));
+                }
+
+                self.insert(Precedence::Path(index), Arc::new(cmd));
             }
         }
 
         Ok(())
     }
 
-    pub fn try_execute(&self, ctx: Context<'_>, input_data: String) -> Result<(), Box<dyn AsStr>> {
-        let parts: Vec<&str> = input_data
-            .split(' ')
-            .map(|item| item.trim())
-            .collect();
+    fn remove_path_folder(&self, index: usize) {
+        let mut storage = self.storage.lock().unwrap();
+        storage.retain(|_, entries| {
+            entries.retain(|entry| entry.precedence != Precedence::Path(index));
+            !entries.is_empty()
+        });
+    }
 
-        match input_data.len() {
-            0 => {
-                println!();
-                Ok(())
-            },
-            1 => {
-                let first = parts.first().unwrap();
-                self.execute(ctx, *first, Vec::new())
+    /// set the extensions that can be left off of PATH commands, PATH
+    /// is read again so the new names show up.
+    pub fn set_path_extensions(&self, extensions: Vec<String>) {
+        *self.path_extensions.lock().unwrap() = extensions;
+        self.forget_path();
+    }
+
+    /// forget everything we know about PATH and read it all again.
+    /// returns how many directories were read.
+    pub fn rehash(&self) -> usize {
+        self.forget_path();
+        self.refresh_path();
+        self.path_folders.lock().unwrap().len()
+    }
+
+    /// drop every PATH command, they're read again when next needed.
+    fn forget_path(&self) {
+        for folder in self.path_folders.lock().unwrap().iter_mut() {
+            folder.modified = None;
+        }
+
+        self.storage.lock().unwrap().retain(|_, entries| {
+            entries.retain(|entry| !matches!(entry.precedence, Precedence::Path(_)));
+            !entries.is_empty()
+        });
+    }
+
+    pub fn try_execute(&self, ctx: &mut Context<'_>, io: &mut IoContext<'_>, input_data: String) -> Result<Outcome, UniError> {
+        let words = lexer::words(&input_data);
+
+        match words.split_first() {
+            None => {
+                writeln!(io.stdout)?;
+                Ok(Outcome::success())
             },
-            _ => {
-                let first = parts.first().unwrap();
-                let rest: Vec<&str> = parts[1..=parts.len()-1].into();
-                self.execute(ctx, *first, rest)
+            Some((first, rest)) => {
+                let rest: Vec<&str> = rest.iter().map(String::as_str).collect();
+                self.execute(ctx, io, first, rest)
             }
         }
     }
 
     pub fn count(&self) -> usize {
-        self.storage.len()
+        self.refresh_path();
+        self.storage.lock().unwrap().len()
     }
-    
-    pub fn iter(&self) -> std::slice::Iter<'_, RefCell<Box<dyn Cmd>>> {
-        self.storage.iter()
+
+    /// the command that wins for each name, sorted by name.
+    pub fn iter(&self) -> std::vec::IntoIter<Arc<dyn Cmd>> {
+        self.refresh_path();
+        let storage = self.storage.lock().unwrap();
+
+        let mut commands: Vec<Arc<dyn Cmd>> = storage
+            .values()
+            .filter_map(|entries| entries.first())
+            .map(|entry| Arc::clone(&entry.command))
+            .collect();
+        commands.sort_by(|a, b| a.name().cmp(b.name()));
+
+        commands.into_iter()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a command that only has a name, and says where it came from.
+    struct Named(&'static str, &'static str);
+
+    impl Cmd for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn desc(&self) -> Option<&str> {
+            Some(self.1)
+        }
+
+        fn docs(&self) -> Option<&str> {
+            None
+        }
+
+        fn execute(&self, _ctx: &mut Context<'_>, _io: &mut IoContext<'_>, _args: Vec<&str>) -> Result<Outcome, UniError> {
+            Ok(Outcome::success())
+        }
+    }
+
+    fn picked(commands: &Commands, name: &str) -> Vec<(Precedence, String)> {
+        commands
+            .get_all(name)
+            .into_iter()
+            .map(|(precedence, command)| (precedence, command.desc().unwrap_or_default().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn commands_are_picked_by_where_they_came_from() {
+        let commands = Commands::new();
+        commands.push_plugin(Box::new(Named("ls", "plugin")));
+        commands.push(Box::new(Named("ls", "builtin")));
+        commands.push_function(Box::new(Named("ls", "function")));
+
+        assert_eq!(picked(&commands, "ls"), [
+            (Precedence::Function, "function".to_string()),
+            (Precedence::Builtin, "builtin".to_string()),
+            (Precedence::Plugin, "plugin".to_string()),
+        ]);
+        assert_eq!(commands.get("ls").unwrap().desc(), Some("function"));
+    }
+
+    #[test]
+    fn the_first_one_added_wins_a_tie() {
+        let commands = Commands::new();
+        commands.push_plugin(Box::new(Named("wave", "first")));
+        commands.push_plugin(Box::new(Named("wave", "second")));
+        assert_eq!(commands.get("wave").unwrap().desc(), Some("first"));
+
+        commands.retain(|command| command.desc() != Some("first"));
+        assert_eq!(commands.get("wave").unwrap().desc(), Some("second"));
+        commands.retain(|_| false);
+        assert!(commands.get("wave").is_none());
+    }
+}
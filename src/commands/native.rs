@@ -6,10 +6,12 @@
 // We iterate through "PATH" and add any executables in those folders
 // into a PathLoadedCommand.
 
-use std::{io, path::Path, cell::Ref};
-use std::process::{Stdio, Command};
-use crate::core::Terminal;
-use super::Cmd;
+use std::{io::{self, Read, Write}, path::Path, thread};
+use std::process::{Child, Stdio, Command};
+use crate::core::signals;
+use is_executable::IsExecutable;
+use super::{io::IoContext, Cmd, Context, Outcome};
+use crate::error::UniError;
 
 pub trait JustGiveMeTheFuckingName {
     fn get_actual_name(&self) -> String;
@@ -29,35 +31,62 @@ pub struct PathLoadedCommand {
 
 impl PathLoadedCommand {
     pub fn new(location: String) -> Result<Self, io::Error> {
-        let file = Path::new(&location);
-        // the command name is the file name with the extension stripped.
-        let command_name = file.get_actual_name();
+        // the command name is the whole file name, dots and all.
+        let name = Path::new(&location).get_actual_name();
+        Ok(Self::with_name(location, name))
+    }
 
-        // We just need to replace everything after the last "."
+    /// a command for `location` that goes by a different name, used for
+    /// the extensionless names of things like `app.exe`.
+    pub fn with_name(location: String, name: String) -> Self {
+        Self {
+            location,
+            name,
+        }
+    }
+}
 
-        // get all positions of dots.
-        let position_of_last_dot: Vec<usize> = command_name
-            .chars()
-            .enumerate()
-            .filter(|(_, p)| if *p == '.' { true } else { false })
-            .map(|(offset, _)| offset)
-            .collect();
+/// `name` without the first extension from `extensions` that it ends
+/// with (ignoring case), if any. `.exe` on its own is left alone.
+pub fn strip_extension<'a>(name: &'a str, extensions: &[String]) -> Option<&'a str> {
+    extensions.iter().find_map(|ext| {
+        if ext.is_empty() || name.len() <= ext.len() {
+            return None;
+        }
+        let split = name.len() - ext.len();
+        if !name.is_char_boundary(split) || !name[split..].eq_ignore_ascii_case(ext) {
+            return None;
+        }
+        Some(&name[..split])
+    })
+}
 
-        // use the last dot as our offset, if there are no dots, replace nothing.
+/// is this a path to a command (like `./app` or `/usr/bin/env`) rather
+/// than a name to look up?
+pub fn is_path_command(name: &str) -> bool {
+    name.contains('/') || (cfg!(windows) && name.contains('\\'))
+}
 
-        let name = if position_of_last_dot.len() != 0 {
-            let last_dot_offset = position_of_last_dot.last().unwrap();
-            &command_name[0..=*last_dot_offset - 1]
-        }
-        else {
-            &command_name
-        };
+/// find the executable at `name`, relative to `cwd` unless it's absolute.
+pub fn resolve_path_command(cwd: &str, name: &str) -> Result<PathLoadedCommand, UniError> {
+    let path = Path::new(cwd).join(name);
 
-        Ok(Self {
-            location,
-            name: name.to_string()
-        })
+    if !path.exists() {
+        return Err(UniError::NotFound(format!("{}: no such file or directory", name)));
     }
+    if path.is_dir() {
+        return Err(UniError::Custom(format!("{}: is a directory", name)));
+    }
+    if !path.is_executable() {
+        return Err(UniError::Custom(format!("{}: permission denied (the file is not executable)", name)));
+    }
+
+    let location = match path.into_os_string().into_string() {
+        Ok(location) => location,
+        Err(_) => return Err(UniError::Custom(format!("{}: the path is not valid unicode", name))),
+    };
+
+    PathLoadedCommand::new(location).map_err(UniError::IoError)
 }
 
 impl Cmd for PathLoadedCommand {
@@ -88,33 +117,101 @@ impl Cmd for PathLoadedCommand {
         Some(self.location.clone())
     }
 
-    fn execute(&self, _ctx: Ref<'_, &Terminal>, args: Vec<&str>) -> Result<(), Box<dyn super::AsStr>> {
+    fn execute(&self, _ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
         let mut command = Command::new(&self.location);
 
-        command.stdout(Stdio::inherit());
-        command.stdin(Stdio::inherit());
-        command.stderr(Stdio::inherit());
-
-        // command.
+        // our own stdio can be handed straight to the child, anything
+        // else has to be copied through pipes.
+        let stdio = |inherited| if inherited { Stdio::inherit() } else { Stdio::piped() };
+        command.stdout(stdio(io.is_inherited()));
+        command.stdin(stdio(io.is_inherited() || io.stdin_is_inherited()));
+        command.stderr(stdio(io.is_inherited()));
+        command.current_dir(&io.cwd);
+        command.env_clear();
+        command.envs(&io.env);
 
         for arg in args {
-            if arg.to_string() == "--trs-sandbox".to_string() {
+            if arg == "--trs-sandbox" {
                 command.env_clear();
                 continue
             }
             command.arg(arg);
         }
 
-        let status = match command.status() {
-            Ok(status) => status.code().unwrap_or(-1),
+        // the child gets its own process group so Ctrl-C can be sent to
+        // it (and anything it starts) without touching us. that means
+        // handing it the terminal, which only the shell that owns it gets
+        // to do. captured commands never touch the terminal.
+        let job_control = io.is_inherited() && signals::installed();
+        #[cfg(unix)]
+        if job_control {
+            use std::os::unix::process::CommandExt;
+            // SAFETY: `enter_own_process_group` only makes async-signal-safe calls.
+            unsafe {
+                command.pre_exec(signals::enter_own_process_group);
+            }
+        }
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
             Err(e) => {
-                println!("failed to execute command ({})", e.to_string());
-                -1
+                writeln!(io.stderr, "failed to execute command ({})", e)?;
+                // what shells use for "found it but couldn't run it".
+                return Ok(Outcome::exited(126));
             }
         };
 
-        println!("{} exited with status ({})", self.name(), status);
+        let job = job_control.then(|| signals::ForegroundJob::new(child.id() as i32));
+        if !io.is_inherited() {
+            pipe_child(&mut child, io)?;
+        }
+        let status = match &job {
+            #[cfg(unix)]
+            Some(job) => job.wait(child.id() as i32, || {
+                let _ = writeln!(io.stderr, "\n{} was stopped, term-rs can't suspend jobs so it's carrying on", self.name());
+            }),
+            _ => child.wait(),
+        };
+        match status {
+            Ok(status) => Ok(Outcome::from(status)),
+            Err(e) => {
+                writeln!(io.stderr, "failed to wait for command ({})", e)?;
+                Ok(Outcome::exited(1))
+            }
+        }
+    }
+}
 
+/// feed `io.stdin` to a child and copy its output into `io`. stdin and
+/// stderr go through threads so a child stuck writing to one pipe can't
+/// stop us from draining the other.
+fn pipe_child(child: &mut Child, io: &mut IoContext<'_>) -> io::Result<()> {
+    let child_stdin = child.stdin.take();
+    let child_stdout = child.stdout.take();
+    let child_stderr = child.stderr.take();
+    let input = &mut io.stdin;
+
+    thread::scope(|scope| {
+        if let Some(mut child_stdin) = child_stdin {
+            scope.spawn(move || {
+                // it's fine for the child to stop reading early.
+                let _ = io::copy(input, &mut child_stdin);
+            });
+        }
+        let errors = child_stderr.map(|mut stderr| {
+            scope.spawn(move || {
+                let mut errors = Vec::new();
+                let _ = stderr.read_to_end(&mut errors);
+                errors
+            })
+        });
+
+        if let Some(mut stdout) = child_stdout {
+            io::copy(&mut stdout, &mut io.stdout)?;
+        }
+        if let Some(errors) = errors {
+            io.stderr.write_all(&errors.join().unwrap_or_default())?;
+        }
         Ok(())
-    }
-}
\ No newline at end of file
+    })
+}
@@ -0,0 +1,426 @@
+// Commands loaded from plugins.
+//
+// Every `.so` and executable in `~/.term-rs/plugins` is loaded at startup,
+// and more can be loaded with the `plugin` builtin. Executables speak
+// json-rpc over stdio, see `rpc.rs`. The rest of this file is about
+// shared libraries.
+//
+// Rust trait objects aren't stable across compilers, so libraries talk to
+// us through a small C ABI instead:
+//
+//   #[no_mangle]
+//   pub extern "C" fn termrs_plugin_v1() -> *const PluginDecl
+//
+// The returned `PluginDecl` (and everything it points to) has to live as
+// long as the library is loaded, a `static` is the easy way to do that.
+// `api_version` must be `PLUGIN_API_VERSION`. When the ABI changes the
+// entry symbol gets a new version suffix, so an old plugin fails to load
+// instead of crashing. Commands can be run on any thread, not just the
+// one that loaded the library.
+
+use std::{cell::RefCell, ffi::{c_char, c_void, CStr, CString}, io::Write, path::Path, sync::{Arc, Mutex, MutexGuard}};
+
+use is_executable::IsExecutable;
+
+use crate::core::settings::Settings;
+use super::{io::IoContext, rpc, Cmd, Commands, Context, Outcome};
+use crate::error::UniError;
+
+pub const PLUGIN_ENTRY_SYMBOL: &str = "termrs_plugin_v1";
+pub const PLUGIN_API_VERSION: u32 = 1;
+pub const PLUGIN_DIR_NAME: &str = "plugins";
+
+#[cfg(windows)]
+pub const PLUGIN_EXTENSION: &str = "dll";
+#[cfg(target_os = "macos")]
+pub const PLUGIN_EXTENSION: &str = "dylib";
+#[cfg(all(unix, not(target_os = "macos")))]
+pub const PLUGIN_EXTENSION: &str = "so";
+
+/// what the shell hands to a plugin command when it runs.
+#[repr(C)]
+pub struct PluginHost {
+    /// write `len` bytes of utf-8 to stdout.
+    pub write_out: extern "C" fn(data: *const u8, len: usize),
+    /// write `len` bytes of utf-8 to stderr.
+    pub write_err: extern "C" fn(data: *const u8, len: usize),
+    /// the working directory, nul terminated.
+    pub cwd: *const c_char,
+}
+
+/// a single command exported by a plugin.
+#[repr(C)]
+pub struct PluginCommandDecl {
+    pub name: *const c_char,
+    /// may be null.
+    pub desc: *const c_char,
+    /// may be null.
+    pub docs: *const c_char,
+    /// run the command, returning its exit status.
+    pub execute: extern "C" fn(host: *const PluginHost, argc: usize, argv: *const *const c_char) -> i32,
+}
+
+/// what `termrs_plugin_v1` returns.
+#[repr(C)]
+pub struct PluginDecl {
+    pub api_version: u32,
+    pub name: *const c_char,
+    pub version: *const c_char,
+    pub commands: *const PluginCommandDecl,
+    pub command_count: usize,
+}
+
+type PluginEntry = extern "C" fn() -> *const PluginDecl;
+
+thread_local! {
+    /// (stdout, stderr) written by the running plugin command, when its
+    /// output isn't going straight to our own stdio. the C ABI has no
+    /// room for a context pointer, so it's kept here instead.
+    static CAPTURED: RefCell<Option<(Vec<u8>, Vec<u8>)>> = const { RefCell::new(None) };
+}
+
+extern "C" fn host_write_out(data: *const u8, len: usize) {
+    if let Some(bytes) = host_bytes(data, len) {
+        CAPTURED.with_borrow_mut(|captured| match captured {
+            Some((out, _)) => out.extend_from_slice(bytes),
+            None => {
+                let _ = std::io::stdout().write_all(bytes);
+            },
+        });
+    }
+}
+
+extern "C" fn host_write_err(data: *const u8, len: usize) {
+    if let Some(bytes) = host_bytes(data, len) {
+        CAPTURED.with_borrow_mut(|captured| match captured {
+            Some((_, err)) => err.extend_from_slice(bytes),
+            None => {
+                let _ = std::io::stderr().write_all(bytes);
+            },
+        });
+    }
+}
+
+fn host_bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if data.is_null() {
+        return None;
+    }
+    // SAFETY: the plugin promises `data` points at `len` readable bytes.
+    Some(unsafe { std::slice::from_raw_parts(data, len) })
+}
+
+/// a handle to a loaded shared library, closed when dropped.
+struct Library {
+    #[cfg(unix)]
+    handle: *mut c_void,
+}
+
+// SAFETY: the handle is only used for dlsym and dlclose, which are safe
+// to call from any thread.
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+#[cfg(unix)]
+impl Library {
+    fn open(path: &Path) -> Result<Library, String> {
+        let c_path = CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|_| "the path contains a nul byte".to_string())?;
+
+        // SAFETY: `c_path` is a valid C string. loading a library runs its
+        // initializers, which is the point of loading a plugin.
+        let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(Self::last_error());
+        }
+
+        Ok(Library { handle })
+    }
+
+    fn symbol(&self, name: &str) -> Result<*mut c_void, String> {
+        let c_name = CString::new(name).map_err(|_| "the symbol contains a nul byte".to_string())?;
+        // SAFETY: `handle` is a library we opened and haven't closed.
+        let symbol = unsafe { libc::dlsym(self.handle, c_name.as_ptr()) };
+        if symbol.is_null() {
+            return Err(format!("it doesn't export `{}`", name));
+        }
+        Ok(symbol)
+    }
+
+    fn last_error() -> String {
+        // SAFETY: dlerror returns null or a valid C string.
+        let error = unsafe { libc::dlerror() };
+        if error.is_null() {
+            return "unknown error".to_string();
+        }
+        // SAFETY: checked for null above.
+        unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Library {
+    fn drop(&mut self) {
+        // SAFETY: every command from this library holds an `Arc` to it,
+        // so nothing can call into it anymore.
+        unsafe {
+            libc::dlclose(self.handle);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl Library {
+    fn open(_path: &Path) -> Result<Library, String> {
+        Err("plugins aren't supported on this platform yet".to_string())
+    }
+
+    fn symbol(&self, _name: &str) -> Result<*mut c_void, String> {
+        Err("plugins aren't supported on this platform yet".to_string())
+    }
+}
+
+/// read a C string from a plugin, `None` when it's null.
+fn plugin_str(ptr: *const c_char) -> Result<Option<String>, String> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    // SAFETY: the plugin promises non-null strings are nul terminated
+    // and live as long as the library.
+    match unsafe { CStr::from_ptr(ptr) }.to_str() {
+        Ok(s) => Ok(Some(s.to_string())),
+        Err(_) => Err("it has a string that isn't valid utf-8".to_string()),
+    }
+}
+
+/// a command that lives in a plugin.
+pub struct PluginCommand {
+    name: String,
+    desc: Option<String>,
+    docs: Option<String>,
+    execute: extern "C" fn(*const PluginHost, usize, *const *const c_char) -> i32,
+    location: String,
+    // keeps the library loaded for as long as this command is around.
+    _library: Arc<Library>,
+}
+
+impl Cmd for PluginCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn desc(&self) -> Option<&str> {
+        self.desc.as_deref()
+    }
+
+    fn docs(&self) -> Option<&str> {
+        self.docs.as_deref()
+    }
+
+    fn is_builtin(&self) -> bool {
+        false
+    }
+
+    fn file_location(&self) -> Option<String> {
+        Some(self.location.clone())
+    }
+
+    fn execute(&self, _ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        let c_args = match args.iter().map(|arg| CString::new(*arg)).collect::<Result<Vec<_>, _>>() {
+            Ok(c_args) => c_args,
+            Err(_) => return Err(UniError::Custom("arguments can't contain nul bytes".to_string())),
+        };
+        let argv: Vec<*const c_char> = c_args.iter().map(|arg| arg.as_ptr()).collect();
+
+        let cwd = CString::new(io.cwd.as_str()).unwrap_or_default();
+        let host = PluginHost {
+            write_out: host_write_out,
+            write_err: host_write_err,
+            cwd: cwd.as_ptr(),
+        };
+
+        if !io.is_inherited() {
+            CAPTURED.set(Some((Vec::new(), Vec::new())));
+        }
+        let status = (self.execute)(&host, argv.len(), argv.as_ptr());
+        if let Some((out, err)) = CAPTURED.take() {
+            io.stdout.write_all(&out)?;
+            io.stderr.write_all(&err)?;
+        }
+
+        Ok(Outcome::exited(status))
+    }
+}
+
+fn is_shared_library(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == PLUGIN_EXTENSION)
+}
+
+/// everything a plugin brought with it, checked and ready to register.
+pub struct PluginParts {
+    pub name: String,
+    pub version: String,
+    pub commands: Vec<Box<dyn Cmd>>,
+}
+
+fn load_library(path: &Path, location: &str) -> Result<PluginParts, String> {
+    let library = Arc::new(Library::open(path)?);
+    let symbol = library.symbol(PLUGIN_ENTRY_SYMBOL)?;
+
+    // SAFETY: the versioned symbol name is the promise that this is a
+    // `PluginEntry`.
+    let entry: PluginEntry = unsafe { std::mem::transmute::<*mut c_void, PluginEntry>(symbol) };
+    let decl = entry();
+    if decl.is_null() {
+        return Err("its entry point returned null".to_string());
+    }
+    // SAFETY: checked for null, and it lives as long as the library.
+    let decl = unsafe { &*decl };
+
+    if decl.api_version != PLUGIN_API_VERSION {
+        return Err(format!(
+            "it was built for plugin api v{}, but this is v{}",
+            decl.api_version, PLUGIN_API_VERSION
+        ));
+    }
+
+    let name = plugin_str(decl.name)?.ok_or_else(|| "it has no name".to_string())?;
+    let version = plugin_str(decl.version)?.unwrap_or_default();
+
+    let decls = if decl.command_count == 0 || decl.commands.is_null() {
+        &[]
+    }
+    else {
+        // SAFETY: the plugin promises `commands` has `command_count` entries.
+        unsafe { std::slice::from_raw_parts(decl.commands, decl.command_count) }
+    };
+
+    // check everything before registering anything, so a bad plugin
+    // doesn't leave half of itself behind.
+    let mut commands: Vec<Box<dyn Cmd>> = Vec::new();
+    for command in decls {
+        let command_name = plugin_str(command.name)?
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| "one of its commands has no name".to_string())?;
+
+        commands.push(Box::new(PluginCommand {
+            name: command_name,
+            desc: plugin_str(command.desc)?,
+            docs: plugin_str(command.docs)?,
+            execute: command.execute,
+            location: location.to_string(),
+            _library: Arc::clone(&library),
+        }));
+    }
+
+    Ok(PluginParts { name, version, commands })
+}
+
+/// a plugin that's currently loaded.
+pub struct LoadedPlugin {
+    pub name: String,
+    pub version: String,
+    pub location: String,
+    pub commands: Vec<String>,
+}
+
+/// keeps track of the plugins we've loaded.
+#[derive(Default)]
+pub struct Plugins {
+    loaded: Mutex<Vec<LoadedPlugin>>,
+}
+
+impl Plugins {
+    pub fn new() -> Plugins {
+        Plugins::default()
+    }
+
+    /// the folder plugins are loaded from at startup.
+    pub fn folder(settings: &Settings) -> Option<String> {
+        let config = settings.config_folder()?;
+        Some(format!("{}/{}", config, PLUGIN_DIR_NAME))
+    }
+
+    /// load every plugin in the plugin folder. a plugin that fails to
+    /// load is reported and skipped.
+    pub fn load_all(&self, commands: &Commands, settings: &Settings) {
+        let folder = match Self::folder(settings) {
+            Some(folder) => folder,
+            None => return,
+        };
+        let entries = match std::fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        let mut paths: Vec<_> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| is_shared_library(path) || (path.is_file() && path.is_executable()))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            if let Err(e) = self.load(commands, &path) {
+                eprintln!("{}", e.render(settings.error_colors()));
+            }
+        }
+    }
+
+    /// load the plugin at `path` and register its commands. shared
+    /// libraries are loaded in process, anything else is started and
+    /// spoken to over json-rpc.
+    pub fn load(&self, commands: &Commands, path: &Path) -> Result<(), UniError> {
+        let location = path.to_string_lossy().into_owned();
+        let failed = |why: String| {
+            UniError::Custom(format!("failed to load plugin {}: {}", location, why))
+        };
+
+        if self.loaded().iter().any(|plugin| plugin.location == location) {
+            return Err(failed("it's already loaded".to_string()));
+        }
+
+        let parts = if is_shared_library(path) {
+            load_library(path, &location)
+        }
+        else {
+            rpc::load(path, &location)
+        }.map_err(failed)?;
+
+        if self.loaded().iter().any(|plugin| plugin.name == parts.name) {
+            return Err(failed(format!("a plugin called \"{}\" is already loaded", parts.name)));
+        }
+
+        let names = parts.commands.iter().map(|command| command.name().to_string()).collect();
+        for command in parts.commands {
+            commands.push_plugin(command);
+        }
+
+        self.loaded().push(LoadedPlugin {
+            name: parts.name,
+            version: parts.version,
+            location,
+            commands: names,
+        });
+
+        Ok(())
+    }
+
+    /// unregister a plugin's commands, the library is closed once the
+    /// last of them is gone.
+    pub fn unload(&self, commands: &Commands, name: &str) -> Result<(), UniError> {
+        let plugin = {
+            let mut loaded = self.loaded();
+            match loaded.iter().position(|plugin| plugin.name == name) {
+                Some(index) => loaded.remove(index),
+                None => return Err(UniError::NotFound(format!("no plugin called \"{}\" is loaded.", name))),
+            }
+        };
+
+        commands.retain(|command| command.file_location().as_deref() != Some(plugin.location.as_str()));
+        Ok(())
+    }
+
+    pub fn loaded(&self) -> MutexGuard<'_, Vec<LoadedPlugin>> {
+        self.loaded.lock().unwrap()
+    }
+}
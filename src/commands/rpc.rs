@@ -0,0 +1,430 @@
+// Plugins that run as their own process and speak json-rpc over stdio.
+//
+// Messages are json-rpc 2.0 objects, one per line. A plugin is started
+// once and kept running, the shell sends requests to its stdin and reads
+// responses and notifications from its stdout. Its stderr is left alone.
+//
+// requests (shell -> plugin):
+//   describe  {"api_version": 1}
+//             -> {"name": "..", "version": "..", "commands": [
+//                    {"name": "..", "desc": "..", "docs": ".."}, ..]}
+//   execute   {"command": "..", "args": [..], "cwd": "..", "env": {..}}
+//             (variables that aren't utf-8 aren't in "env")
+//             -> {"status": 0}
+//
+// notifications (plugin -> shell), while `execute` is running:
+//   output    {"stream": "stdout" | "stderr", "data": ".."}
+//
+// notifications (shell -> plugin):
+//   cancel    {"id": n}, Ctrl-C was pressed while request n was running.
+//   shutdown  {}, the plugin is being unloaded and should exit.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::core::signals;
+use super::{io::IoContext, plugin::PluginParts, Cmd, Context, Outcome};
+use crate::error::UniError;
+
+pub const RPC_API_VERSION: u32 = 1;
+
+/// how long a plugin gets to describe itself before we give up on it.
+const DESCRIBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// how long a plugin gets to stop after Ctrl-C before we kill it.
+const CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+/// how often we check for Ctrl-C while waiting on a plugin.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RpcCommandInfo {
+    pub name: String,
+    #[serde(default)]
+    pub desc: Option<String>,
+    #[serde(default)]
+    pub docs: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RpcDescription {
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub commands: Vec<RpcCommandInfo>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// a running plugin process.
+pub struct RpcPlugin {
+    child: Mutex<Child>,
+    stdin: Mutex<Option<ChildStdin>>,
+    /// lines from the plugin's stdout, read on another thread so we
+    /// can give up waiting on them. held for the whole of a request,
+    /// so only one runs at a time.
+    messages: Mutex<Receiver<String>>,
+    next_id: AtomicU64,
+}
+
+impl RpcPlugin {
+    pub fn spawn(path: &Path) -> Result<RpcPlugin, String> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("failed to start it ({})", e))?;
+
+        let stdin = child.stdin.take();
+        let stdout = match child.stdout.take() {
+            Some(stdout) => stdout,
+            None => return Err("failed to read its output".to_string()),
+        };
+
+        let (sender, messages) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(RpcPlugin {
+            child: Mutex::new(child),
+            stdin: Mutex::new(stdin),
+            messages: Mutex::new(messages),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    fn send(&self, message: &Value) -> Result<(), String> {
+        let mut stdin = self.stdin.lock().unwrap();
+        let stdin = match stdin.as_mut() {
+            Some(stdin) => stdin,
+            None => return Err("it isn't running".to_string()),
+        };
+
+        writeln!(stdin, "{}", message)
+            .and_then(|_| stdin.flush())
+            .map_err(|e| format!("failed to talk to it ({})", e))
+    }
+
+    fn notify(&self, method: &str, params: Value) -> Result<(), String> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    /// stop the plugin for good, its commands fail from then on.
+    fn kill(&self) {
+        self.stdin.lock().unwrap().take();
+        let mut child = self.child.lock().unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    /// send a request and wait for its result. notifications that show
+    /// up in the meantime go to `on_output`.
+    fn request(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Option<Duration>,
+        on_output: &mut dyn FnMut(Stream, &str),
+    ) -> Result<Value, String> {
+        let messages = self.messages.lock().unwrap();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+
+        let started = Instant::now();
+        let mut cancelled: Option<Instant> = None;
+
+        loop {
+            let line = match messages.recv_timeout(POLL_INTERVAL) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    if timeout.is_some_and(|timeout| started.elapsed() > timeout) {
+                        return Err(format!("it didn't answer \"{}\" in time", method));
+                    }
+                    match cancelled {
+                        Some(at) if at.elapsed() > CANCEL_TIMEOUT => {
+                            self.kill();
+                            return Err(format!("it didn't stop \"{}\" when asked, so it was killed", method));
+                        },
+                        None if signals::interrupted() => {
+                            cancelled = Some(Instant::now());
+                            self.notify("cancel", json!({ "id": id }))?;
+                        },
+                        _ => {},
+                    }
+                    continue;
+                },
+                Err(RecvTimeoutError::Disconnected) => return Err("it exited".to_string()),
+            };
+
+            let message: Value = match serde_json::from_str(&line) {
+                Ok(message) => message,
+                Err(e) => return Err(format!("it sent something that isn't json ({})", e)),
+            };
+
+            // a notification.
+            if message.get("id").is_none() {
+                if message["method"] == "output" {
+                    let stream = match message["params"]["stream"].as_str() {
+                        Some("stderr") => Stream::Stderr,
+                        _ => Stream::Stdout,
+                    };
+                    on_output(stream, message["params"]["data"].as_str().unwrap_or(""));
+                }
+                continue;
+            }
+
+            if message["id"] != id {
+                continue;
+            }
+
+            if let Some(error) = message.get("error") {
+                let text = error["message"].as_str().unwrap_or("unknown error");
+                return Err(text.to_string());
+            }
+
+            return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    /// ask the plugin what it is and which commands it has.
+    pub fn describe(&self) -> Result<RpcDescription, String> {
+        let params = json!({ "api_version": RPC_API_VERSION });
+        let result = self.request("describe", params, Some(DESCRIBE_TIMEOUT), &mut |_, _| {})?;
+        serde_json::from_value(result).map_err(|e| format!("it described itself wrong ({})", e))
+    }
+
+    /// run one of the plugin's commands, returning its exit status.
+    pub fn execute(
+        &self,
+        command: &str,
+        args: &[&str],
+        cwd: &str,
+        env: HashMap<String, String>,
+        mut on_output: impl FnMut(Stream, &str),
+    ) -> Result<i32, String> {
+        let params = json!({
+            "command": command,
+            "args": args,
+            "cwd": cwd,
+            "env": env,
+        });
+        let result = self.request("execute", params, None, &mut on_output)?;
+
+        match result["status"].as_i64() {
+            Some(status) => Ok(status as i32),
+            None => Err("it didn't say how the command went".to_string()),
+        }
+    }
+}
+
+impl Drop for RpcPlugin {
+    fn drop(&mut self) {
+        let _ = self.notify("shutdown", json!({}));
+        // closing stdin is the other hint that it's time to go.
+        self.stdin.get_mut().unwrap_or_else(PoisonError::into_inner).take();
+
+        let child = self.child.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let deadline = Instant::now() + Duration::from_millis(200);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = child.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// a command that forwards to a plugin process.
+pub struct RpcCommand {
+    info: RpcCommandInfo,
+    location: String,
+    plugin: Arc<RpcPlugin>,
+}
+
+impl Cmd for RpcCommand {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    fn desc(&self) -> Option<&str> {
+        self.info.desc.as_deref()
+    }
+
+    fn docs(&self) -> Option<&str> {
+        self.info.docs.as_deref()
+    }
+
+    fn is_builtin(&self) -> bool {
+        false
+    }
+
+    fn file_location(&self) -> Option<String> {
+        Some(self.location.clone())
+    }
+
+    fn execute(&self, _ctx: &mut Context<'_>, io: &mut IoContext<'_>, args: Vec<&str>) -> Result<Outcome, UniError> {
+        // json only has room for utf-8, anything else is left out.
+        let env = io.env
+            .iter()
+            .filter_map(|(key, value)| Some((key.to_str()?.to_string(), value.to_str()?.to_string())))
+            .collect();
+        let status = self.plugin
+            .execute(self.name(), &args, &io.cwd, env, |stream, data| {
+                let out = match stream {
+                    Stream::Stdout => &mut io.stdout,
+                    Stream::Stderr => &mut io.stderr,
+                };
+                let _ = out.write_all(data.as_bytes());
+                let _ = out.flush();
+            })
+            .map_err(|e| UniError::Custom(format!("{}: {}", self.name(), e)))?;
+
+        Ok(Outcome::exited(status))
+    }
+}
+
+/// start the plugin at `path` and wrap up its commands.
+pub fn load(path: &Path, location: &str) -> Result<PluginParts, String> {
+    let plugin = Arc::new(RpcPlugin::spawn(path)?);
+    let description = plugin.describe()?;
+
+    if let Some(command) = description.commands.iter().find(|command| command.name.is_empty()) {
+        return Err(format!("one of its commands has no name ({:?})", command));
+    }
+
+    let commands = description.commands
+        .into_iter()
+        .map(|info| Box::new(RpcCommand {
+            info,
+            location: location.to_string(),
+            plugin: Arc::clone(&plugin),
+        }) as Box<dyn Cmd>)
+        .collect();
+
+    Ok(PluginParts {
+        name: description.name,
+        version: description.version,
+        commands,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{os::unix::fs::PermissionsExt, path::PathBuf};
+
+    /// a plugin written in sh that answers with canned responses.
+    const STUB: &str = r#"#!/bin/sh
+while read -r line; do
+    id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+    case "$line" in
+        *'"describe"'*)
+            printf '%s\n' '{"jsonrpc":"2.0","id":'"$id"',"result":{"name":"stub","version":"1.2.3","commands":[{"name":"greet","desc":"say hi"},{"name":"fail"}]}}'
+            ;;
+        *'"command":"greet"'*)
+            printf '%s\n' '{"jsonrpc":"2.0","method":"output","params":{"stream":"stdout","data":"hello\n"}}'
+            printf '%s\n' '{"jsonrpc":"2.0","method":"output","params":{"stream":"stderr","data":"careful\n"}}'
+            printf '%s\n' '{"jsonrpc":"2.0","id":'"$id"',"result":{"status":3}}'
+            ;;
+        *'"command":"fail"'*)
+            printf '%s\n' '{"jsonrpc":"2.0","id":'"$id"',"error":{"code":1,"message":"it broke"}}'
+            ;;
+        *'"shutdown"'*)
+            exit 0
+            ;;
+    esac
+done
+"#;
+
+    fn write_stub(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("term-rs-rpc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn describe_lists_the_commands() {
+        let plugin = RpcPlugin::spawn(&write_stub("describe", STUB)).unwrap();
+        let description = plugin.describe().unwrap();
+
+        assert_eq!(description.name, "stub");
+        assert_eq!(description.version, "1.2.3");
+        assert_eq!(description.commands, vec![
+            RpcCommandInfo { name: "greet".to_string(), desc: Some("say hi".to_string()), docs: None },
+            RpcCommandInfo { name: "fail".to_string(), desc: None, docs: None },
+        ]);
+    }
+
+    #[test]
+    fn execute_streams_output_and_returns_the_status() {
+        let plugin = RpcPlugin::spawn(&write_stub("execute", STUB)).unwrap();
+
+        let mut output = Vec::new();
+        let status = plugin
+            .execute("greet", &["a", "b c"], "/", HashMap::new(), |stream, data| {
+                output.push((stream, data.to_string()));
+            })
+            .unwrap();
+
+        assert_eq!(status, 3);
+        assert_eq!(output, vec![
+            (Stream::Stdout, "hello\n".to_string()),
+            (Stream::Stderr, "careful\n".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn errors_from_the_plugin_are_reported() {
+        let plugin = RpcPlugin::spawn(&write_stub("error", STUB)).unwrap();
+        let result = plugin.execute("fail", &[], "/", HashMap::new(), |_, _| {});
+        assert_eq!(result, Err("it broke".to_string()));
+    }
+
+    #[test]
+    fn a_plugin_that_exits_fails_to_load() {
+        let path = write_stub("exits", "#!/bin/sh\nexit 1\n");
+        // depending on timing it's either gone before or after we ask.
+        assert!(load(&path, &path.to_string_lossy()).is_err());
+    }
+
+    #[test]
+    fn load_wraps_every_command() {
+        let path = write_stub("load", STUB);
+        let parts = load(&path, &path.to_string_lossy()).unwrap();
+
+        let names: Vec<&str> = parts.commands.iter().map(|command| command.name()).collect();
+        assert_eq!(parts.name, "stub");
+        assert_eq!(names, vec!["greet", "fail"]);
+        assert_eq!(parts.commands[0].desc(), Some("say hi"));
+        assert_eq!(parts.commands[0].file_location(), Some(path.to_string_lossy().into_owned()));
+    }
+}
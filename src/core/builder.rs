@@ -0,0 +1,222 @@
+// Putting a terminal together.
+//
+// `Terminal::new` is the shell the way it runs from the command line.
+// The builder is for embedding it: pick the builtins, add commands of
+// your own, and point it at other streams and another config folder.
+
+use std::{ffi::OsString, io::{BufRead, Write}, time::Instant};
+
+use crate::commands::{impls, io::{Env, Streams}, plugin::Plugins, Cmd, Commands};
+use crate::error::{TerminalInitError, UniError};
+use super::{session::Session, settings::Settings, Terminal};
+
+#[cfg(windows)]
+const USER_NAME_ENV_NAME: &str = "USERNAME";
+#[cfg(not(windows))]
+const USER_NAME_ENV_NAME: &str = "USER";
+
+#[cfg(windows)]
+const PATH_ENVVAR_SEP: char = ';';
+#[cfg(not(windows))]
+const PATH_ENVVAR_SEP: char = ':';
+
+const PATH_ENVIRONMENT_VAR: &str = "PATH";
+
+pub struct TerminalBuilder {
+    interactive: bool,
+    /// the builtins to keep, all of them when `None`.
+    builtins: Option<Vec<String>>,
+    commands: Vec<Box<dyn Cmd>>,
+    config_folder: Option<String>,
+    current_dir: Option<String>,
+    /// set on top of the process's environment.
+    env: Vec<(OsString, OsString)>,
+    streams: Streams,
+    scan_path: bool,
+    path_folders: Vec<String>,
+}
+
+impl Default for TerminalBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TerminalBuilder {
+    /// a non-interactive terminal with every builtin, the usual config
+    /// folder, the process's stdio and PATH.
+    pub fn new() -> TerminalBuilder {
+        TerminalBuilder {
+            interactive: false,
+            builtins: None,
+            commands: Vec::new(),
+            config_folder: Settings::default_config_folder(),
+            current_dir: None,
+            env: Vec::new(),
+            streams: Streams::Inherit,
+            scan_path: true,
+            path_folders: Vec::new(),
+        }
+    }
+
+    /// prompt, use colors and save the settings when exiting, like when
+    /// the shell is run from a terminal.
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
+    /// only keep the builtins called one of `names`, none at all when
+    /// it's empty.
+    pub fn builtins(mut self, names: &[&str]) -> Self {
+        self.builtins = Some(names.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    /// add a command of your own. it wins over anything else with the
+    /// same name.
+    pub fn command(mut self, command: Box<dyn Cmd>) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    /// where settings.json and the plugins folder are, instead of
+    /// `~/.term-rs`.
+    pub fn config_folder(mut self, folder: impl Into<String>) -> Self {
+        self.config_folder = Some(folder.into());
+        self
+    }
+
+    /// start in `dir` instead of the current directory, or wherever the
+    /// last interactive session was left.
+    pub fn current_dir(mut self, dir: impl Into<String>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// set `key` in the environment commands run with, the process's own
+    /// environment is left alone.
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// run commands with these instead of the process's stdio.
+    pub fn streams(
+        mut self,
+        stdin: impl BufRead + Send + 'static,
+        stdout: impl Write + 'static,
+        stderr: impl Write + 'static,
+    ) -> Self {
+        self.streams = Streams::Custom {
+            stdin: Box::new(stdin),
+            stdout: Box::new(stdout),
+            stderr: Box::new(stderr),
+        };
+        self
+    }
+
+    /// look for commands in the folders in PATH, on by default.
+    pub fn scan_path(mut self, scan: bool) -> Self {
+        self.scan_path = scan;
+        self
+    }
+
+    /// look for commands in `folder` too, before anything in PATH.
+    pub fn path_folder(mut self, folder: impl Into<String>) -> Self {
+        self.path_folders.push(folder.into());
+        self
+    }
+
+    pub fn build(self) -> Result<Terminal, UniError> {
+        let started = Instant::now();
+        let mut commands = Commands::new();
+
+        let builtins = impls::builtins();
+        if let Some(wanted) = &self.builtins {
+            if let Some(unknown) = wanted.iter().find(|name| !builtins.iter().any(|builtin| builtin.name() == *name)) {
+                return Err(UniError::NotFound(format!("there is no builtin called \"{}\".", unknown)));
+            }
+        }
+        for builtin in builtins {
+            let keep = match &self.builtins {
+                Some(wanted) => wanted.iter().any(|name| name == builtin.name()),
+                None => true,
+            };
+            if keep {
+                commands.push(builtin);
+            }
+        }
+
+        for command in self.commands {
+            commands.push_function(command);
+        }
+
+        for folder in self.path_folders {
+            commands.add_path_folder(folder);
+        }
+
+        if self.scan_path {
+            let path = match std::env::var(PATH_ENVIRONMENT_VAR) {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    eprintln!("failed to get `PATH` environment variable. ({})", e);
+                    None
+                }
+            };
+
+            if let Some(path) = path {
+                let all_directorys: Vec<&str> = path.split(PATH_ENVVAR_SEP).collect();
+                for dir in all_directorys {
+                    commands.add_path_folder(dir.to_string());
+                }
+            }
+        }
+
+        let explicit_dir = self.current_dir.is_some();
+        let current_path = match self.current_dir {
+            Some(dir) => dir,
+            None => match std::env::current_dir() {
+                Ok(path) => {
+                    let p = path.into_os_string();
+                    p.into_string().unwrap()
+                },
+                Err(e) => {
+                    return Err(
+                        TerminalInitError::CantGetDirectory(e).into()
+                    );
+                }
+            },
+        };
+
+        // TODO: check if user even wants their name shown.
+        // IF we cant find the user name, just dont use one.
+        let user_name = std::env::var(USER_NAME_ENV_NAME).ok();
+
+        let mut prompt = Settings::from_save_or_default(self.config_folder, current_path.clone());
+        prompt.user_name = user_name;
+        prompt.colors = self.interactive;
+
+        commands.set_path_extensions(prompt.path_extensions.clone());
+
+        let plugins = Plugins::new();
+        plugins.load_all(&commands, &prompt);
+
+        // scripts expect to run where they were started, not wherever
+        // the last interactive session was left.
+        if !self.interactive || explicit_dir {
+            prompt.path = current_path;
+        }
+
+        let mut env: Env = std::env::vars_os().collect();
+        env.extend(self.env);
+
+        Ok(Terminal {
+            cmds: commands,
+            plugins,
+            session: Session::new(prompt, env, self.interactive),
+            streams: self.streams,
+            startup_time: started.elapsed(),
+        })
+    }
+}
@@ -0,0 +1,260 @@
+// Tab completion.
+//
+// The first word on the line completes to command names. After that we
+// ask the command itself through `Cmd::complete`, and when it doesn't
+// have an opinion we complete paths relative to the working directory.
+
+use std::path::Path;
+
+use crate::commands::{native::{is_path_command, resolve_path_command}, Context};
+use super::{editor::Helper, highlight::highlight, lexer::{quote, split_commands, tokenize, words, TokenKind}};
+
+/// a single thing that the word under the cursor could become.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completion {
+    pub value: String,
+    pub desc: Option<String>,
+}
+
+impl Completion {
+    pub fn new(value: impl Into<String>) -> Completion {
+        Completion {
+            value: value.into(),
+            desc: None,
+        }
+    }
+
+    pub fn with_desc(value: impl Into<String>, desc: impl Into<String>) -> Completion {
+        Completion {
+            value: value.into(),
+            desc: Some(desc.into()),
+        }
+    }
+}
+
+/// complete single character flags, `flags` is a list of (flag, description).
+///
+/// flags can be grouped (`-cU`), so a word that's already a group of flags
+/// completes to itself plus each flag that isn't in it yet.
+pub fn complete_flags(word: &str, flags: &[(char, &str)]) -> Vec<Completion> {
+    if !word.starts_with('-') {
+        return Vec::new();
+    }
+
+    let present = &word[1..];
+    flags
+        .iter()
+        .filter(|(flag, _)| !present.contains(*flag))
+        .map(|(flag, desc)| Completion::with_desc(format!("{}{}", word, flag), *desc))
+        .collect()
+}
+
+/// complete whole options like `--json` or `-n`, `options` is a
+/// list of (option, description).
+pub fn complete_options(word: &str, options: &[(&str, &str)]) -> Vec<Completion> {
+    if !word.starts_with('-') {
+        return Vec::new();
+    }
+
+    options
+        .iter()
+        .filter(|(option, _)| option.starts_with(word))
+        .map(|(option, desc)| Completion::with_desc(*option, *desc))
+        .collect()
+}
+
+/// complete the names of every command we know about.
+pub fn complete_commands(ctx: &Context<'_>, word: &str) -> Vec<Completion> {
+    let mut result: Vec<Completion> = Vec::new();
+
+    for command in ctx.commands.iter() {
+        let name = command.name();
+
+        if !name.starts_with(word) {
+            continue;
+        }
+
+        result.push(match command.desc() {
+            Some(desc) => Completion::with_desc(name, desc),
+            None => Completion::new(name),
+        });
+    }
+
+    result
+}
+
+/// complete a path relative to the current working directory.
+/// directories complete with a trailing `/` so you can keep going.
+pub fn complete_paths(ctx: &Context<'_>, word: &str, dirs_only: bool) -> Vec<Completion> {
+    // split "some/dir/fi" into "some/dir/" and "fi"
+    let (dir_part, file_part) = match word.rfind('/') {
+        Some(offset) => word.split_at(offset + 1),
+        None => ("", word),
+    };
+
+    let search_dir = if Path::new(dir_part).is_absolute() {
+        dir_part.to_string()
+    }
+    else {
+        format!("{}/{}", ctx.cwd(), dir_part)
+    };
+
+    let entries = match std::fs::read_dir(search_dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut result = Vec::new();
+
+    for entry in entries.flatten() {
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+
+        // hidden files only show up when you ask for them.
+        if !name.starts_with(file_part) || (name.starts_with('.') && !file_part.starts_with('.')) {
+            continue;
+        }
+
+        let is_dir = entry.path().is_dir();
+        if dirs_only && !is_dir {
+            continue;
+        }
+
+        let suffix = if is_dir { "/" } else { "" };
+        result.push(Completion::new(format!("{}{}{}", dir_part, name, suffix)));
+    }
+
+    result.sort_by(|a, b| a.value.cmp(&b.value));
+    result
+}
+
+/// split the line up to the cursor into the word being completed and the
+/// words of its command before it, the way the command will see them.
+/// the word starts at the returned char offset and has its quotes taken
+/// off, so `cd "My D` completes `My D`.
+fn word_under_cursor(before: &str) -> (usize, String, Vec<String>) {
+    let start = tokenize(before)
+        .iter()
+        .rev()
+        .take_while(|token| !matches!(token.kind, TokenKind::Whitespace | TokenKind::Operator))
+        .last()
+        .map_or(before.len(), |token| token.start);
+
+    let word = words(&before[start..]).pop().unwrap_or_default();
+    let command = split_commands(&before[..start]).pop().map_or("", |(_, command)| command);
+    (before[..start].chars().count(), word, words(command))
+}
+
+/// the `Helper` the line editor uses when the user is typing commands.
+pub struct TerminalHelper<'a> {
+    ctx: &'a Context<'a>,
+}
+
+impl<'a> TerminalHelper<'a> {
+    pub fn new(ctx: &'a Context<'a>) -> TerminalHelper<'a> {
+        TerminalHelper { ctx }
+    }
+}
+
+impl Helper for TerminalHelper<'_> {
+    fn complete(&self, line: &str, pos: usize) -> (usize, Vec<Completion>) {
+        let before: String = line.chars().take(pos).collect();
+        let (start, word, words) = word_under_cursor(&before);
+
+        let mut candidates = match words.split_first() {
+            None if is_path_command(&word) => complete_paths(self.ctx, &word, false),
+            None => complete_commands(self.ctx, &word),
+            Some((name, rest)) => {
+                let mut args: Vec<&str> = rest.iter().map(String::as_str).collect();
+                args.push(&word);
+                let cursor = args.len() - 1;
+
+                let from_command = match self.ctx.commands.get(name) {
+                    Some(command) => command.complete(self.ctx, &args, cursor),
+                    None => None,
+                };
+
+                match from_command {
+                    Some(candidates) => candidates,
+                    None => complete_paths(self.ctx, &word, false),
+                }
+            }
+        };
+
+        candidates.retain(|c| c.value.starts_with(&word));
+        // they replace the word as it was typed, quotes and all.
+        for candidate in &mut candidates {
+            candidate.value = quote(&candidate.value);
+        }
+        (start, candidates)
+    }
+
+    fn highlight(&self, line: &str) -> String {
+        let commands = self.ctx.commands;
+        highlight(line, &self.ctx.session.settings, |name| {
+            if is_path_command(name) {
+                return resolve_path_command(self.ctx.cwd(), name).is_ok();
+            }
+            commands.get(name).is_some()
+        })
+    }
+
+    fn hint(&self, line: &str) -> Option<String> {
+        let suggestion = self.ctx.session.history.suggest(line, self.ctx.cwd())?;
+        Some(suggestion[line.len()..].to_string())
+    }
+
+    fn highlight_hint(&self, hint: &str) -> String {
+        let settings = &self.ctx.session.settings;
+        settings.paint(hint, &settings.highlight.suggestion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(before: &str) -> (usize, String, Vec<String>) {
+        word_under_cursor(before)
+    }
+
+    fn owned(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn the_first_word_is_the_command() {
+        assert_eq!(split(""), (0, String::new(), Vec::new()));
+        assert_eq!(split("gi"), (0, "gi".to_string(), Vec::new()));
+        assert_eq!(split("  gi"), (2, "gi".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn later_words_come_with_the_command() {
+        assert_eq!(split("cd  Do"), (4, "Do".to_string(), owned(&["cd"])));
+        assert_eq!(split("ls -a "), (6, String::new(), owned(&["ls", "-a"])));
+    }
+
+    #[test]
+    fn quotes_are_respected() {
+        assert_eq!(split("cd \"My D"), (3, "My D".to_string(), owned(&["cd"])));
+        assert_eq!(split("cd 'My D"), (3, "My D".to_string(), owned(&["cd"])));
+        assert_eq!(split("cp \"a b\" c"), (9, "c".to_string(), owned(&["cp", "a b"])));
+        assert_eq!(split("cd \"My Dir\"/s"), (3, "My Dir/s".to_string(), owned(&["cd"])));
+    }
+
+    #[test]
+    fn only_the_command_under_the_cursor_counts() {
+        assert_eq!(split("ls; cd Do"), (7, "Do".to_string(), owned(&["cd"])));
+        assert_eq!(split("true && gi"), (8, "gi".to_string(), Vec::new()));
+        assert_eq!(split("echo a;gi"), (7, "gi".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn offsets_are_in_chars() {
+        assert_eq!(split("cd é/ü"), (3, "é/ü".to_string(), owned(&["cd"])));
+        assert_eq!(split("ü ö"), (2, "ö".to_string(), owned(&["ü"])));
+    }
+}
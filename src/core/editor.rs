@@ -0,0 +1,1268 @@
+// A small line editor for the interactive prompt.
+//
+// While a line is being read the terminal is switched out of canonical
+// mode so we see every key press as it happens (tab, arrows, ...). The
+// bindings follow the usual emacs/readline defaults.
+
+use std::io::{self, Write};
+
+use super::completion::Completion;
+use super::input::HistoryEntry;
+use super::vi::{self, Action, InsertAt, Mode, Motion, Operator, Target};
+
+/// Things the editor needs to ask the rest of the shell about.
+pub trait Helper {
+    /// complete the word that ends at `pos` (a char offset into `line`).
+    /// returns the char offset where the replaced word starts and
+    /// the candidates that could replace it.
+    fn complete(&self, line: &str, pos: usize) -> (usize, Vec<Completion>);
+
+    /// color `line` for display, the result must take up the same
+    /// number of columns as `line` once escape codes are ignored.
+    fn highlight(&self, line: &str) -> String;
+
+    /// what could come after `line`, shown as ghost text after the cursor.
+    fn hint(&self, line: &str) -> Option<String>;
+
+    /// color the ghost text returned by `hint`.
+    fn highlight_hint(&self, hint: &str) -> String;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    Char(char),
+    /// control + a letter, the letter is always lowercase.
+    Ctrl(char),
+    /// alt (or escape followed by) a character.
+    Alt(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Escape,
+    Unknown,
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::io;
+
+    /// puts the terminal into non-canonical, no-echo mode for as long
+    /// as it is alive.
+    pub struct RawMode {
+        original: libc::termios,
+    }
+
+    impl RawMode {
+        pub fn enable() -> io::Result<RawMode> {
+            // SAFETY: termios is plain old data, and tcgetattr fills it in.
+            unsafe {
+                let mut attrs: libc::termios = std::mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut attrs) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let original = attrs;
+
+                // ISIG is off too, Ctrl-C reaches us as a key and cancels the line.
+                attrs.c_lflag &= !(libc::ICANON | libc::ECHO | libc::IEXTEN | libc::ISIG);
+                attrs.c_iflag &= !(libc::IXON | libc::ICRNL);
+                attrs.c_cc[libc::VMIN] = 1;
+                attrs.c_cc[libc::VTIME] = 0;
+
+                if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &attrs) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(RawMode { original })
+            }
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            // SAFETY: restoring the attributes we read in `enable`.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original);
+            }
+        }
+    }
+
+    /// read a single byte from stdin, bypassing any buffering.
+    pub fn read_byte() -> io::Result<Option<u8>> {
+        let mut byte = 0u8;
+        loop {
+            // SAFETY: reading one byte into a valid one byte buffer.
+            let n = unsafe {
+                libc::read(libc::STDIN_FILENO, &mut byte as *mut u8 as *mut libc::c_void, 1)
+            };
+            match n {
+                1 => return Ok(Some(byte)),
+                0 => return Ok(None),
+                _ => {
+                    let e = io::Error::last_os_error();
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// wait up to `millis` for stdin to have something to read.
+    pub fn has_input(millis: i32) -> bool {
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: polling a single valid pollfd.
+        unsafe { libc::poll(&mut fd, 1, millis) > 0 }
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use std::io;
+
+    pub struct RawMode;
+
+    impl RawMode {
+        pub fn enable() -> io::Result<RawMode> {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "raw mode is only supported on unix"))
+        }
+    }
+
+    pub fn read_byte() -> io::Result<Option<u8>> {
+        Ok(None)
+    }
+
+    pub fn has_input(_millis: i32) -> bool {
+        false
+    }
+}
+
+pub use sys::RawMode;
+
+/// how long to wait after an escape byte to see if it starts a sequence.
+const ESCAPE_TIMEOUT_MS: i32 = 30;
+
+fn read_char(first: u8) -> io::Result<char> {
+    let len = match first {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        _ => 4,
+    };
+
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        match sys::read_byte()? {
+            Some(b) => bytes.push(b),
+            None => break,
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&bytes).chars().next().unwrap_or('\u{fffd}'))
+}
+
+fn read_escape() -> io::Result<Key> {
+    if !sys::has_input(ESCAPE_TIMEOUT_MS) {
+        return Ok(Key::Escape);
+    }
+
+    let next = match sys::read_byte()? {
+        Some(b) => b,
+        None => return Ok(Key::Escape),
+    };
+
+    match next {
+        b'[' => {
+            // CSI: parameter bytes followed by a single final byte.
+            let mut params = String::new();
+            let last = loop {
+                match sys::read_byte()? {
+                    Some(b) if (0x40..=0x7e).contains(&b) => break b,
+                    Some(b) => params.push(b as char),
+                    None => return Ok(Key::Unknown),
+                }
+            };
+
+            Ok(match (last, params.as_str()) {
+                (b'A', _) => Key::Up,
+                (b'B', _) => Key::Down,
+                (b'C', _) => Key::Right,
+                (b'D', _) => Key::Left,
+                (b'H', _) => Key::Home,
+                (b'F', _) => Key::End,
+                (b'~', "1") | (b'~', "7") => Key::Home,
+                (b'~', "4") | (b'~', "8") => Key::End,
+                (b'~', "3") => Key::Delete,
+                _ => Key::Unknown,
+            })
+        },
+        b'O' => {
+            Ok(match sys::read_byte()? {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                Some(b'H') => Key::Home,
+                Some(b'F') => Key::End,
+                _ => Key::Unknown,
+            })
+        },
+        0x7f => Ok(Key::Alt('\x7f')),
+        b => Ok(Key::Alt(read_char(b)?)),
+    }
+}
+
+/// block until a key is pressed. `None` means stdin was closed.
+pub fn read_key() -> io::Result<Option<Key>> {
+    let byte = match sys::read_byte()? {
+        Some(b) => b,
+        None => return Ok(None),
+    };
+
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x1b => read_escape()?,
+        0x01..=0x1a => Key::Ctrl((b'a' + byte - 1) as char),
+        0x00..=0x1f => Key::Unknown,
+        _ => Key::Char(read_char(byte)?),
+    };
+
+    Ok(Some(key))
+}
+
+/// the number of columns `s` takes up, ignoring ansi escape sequences.
+pub fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // skip up to and including the final byte of the sequence.
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        width += 1;
+    }
+
+    width
+}
+
+fn terminal_width() -> usize {
+    match termsize::get() {
+        Some(size) if size.cols > 0 => size.cols as usize,
+        _ => 80,
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+pub struct LineEditor<'a> {
+    prompt: &'a str,
+    history: &'a [HistoryEntry],
+    helper: &'a dyn Helper,
+
+    buf: Vec<char>,
+    pos: usize,
+
+    /// where we are in `history`, `history.len()` is the line being edited.
+    hist_index: usize,
+    /// the line being edited before we started walking history.
+    saved: Vec<char>,
+    /// the last thing killed with ctrl-k/ctrl-u/ctrl-w.
+    kill_ring: Vec<char>,
+    /// the suggestion currently shown after the cursor.
+    hint: Vec<char>,
+    /// set when editing with vi bindings instead of emacs ones.
+    vi: Option<vi::State>,
+
+    /// how many rows below the start of the prompt the cursor is.
+    cursor_row: usize,
+    /// the terminal. only `read_line` reads keys from it, the rest just
+    /// writes here, so editing works without one.
+    out: Box<dyn Write + 'a>,
+}
+
+impl<'a> LineEditor<'a> {
+    pub fn new(prompt: &'a str, history: &'a [HistoryEntry], helper: &'a dyn Helper) -> LineEditor<'a> {
+        LineEditor {
+            prompt,
+            history,
+            helper,
+            buf: Vec::new(),
+            pos: 0,
+            hist_index: history.len(),
+            saved: Vec::new(),
+            kill_ring: Vec::new(),
+            hint: Vec::new(),
+            vi: None,
+            cursor_row: 0,
+            out: Box::new(io::stdout()),
+        }
+    }
+
+    /// edit with vi bindings, starting in insert mode.
+    pub fn vi_mode(mut self, enabled: bool) -> LineEditor<'a> {
+        self.vi = if enabled { Some(vi::State::new()) } else { None };
+        self
+    }
+
+    /// read a line from the user. `None` means stdin was closed (or Ctrl-D
+    /// on an empty line), and an
+    /// `Interrupted` error means the line was cancelled with Ctrl-C.
+    pub fn read_line(mut self) -> io::Result<Option<String>> {
+        let _raw = RawMode::enable()?;
+        self.refresh()?;
+
+        loop {
+            let key = match read_key()? {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+
+            if key == Key::Enter {
+                // don't leave the suggestion behind on the finished line.
+                self.hint.clear();
+                self.refresh_line()?;
+                self.move_to_end()?;
+                write!(self.out, "\r\n")?;
+                self.out.flush()?;
+                return Ok(Some(self.line()));
+            }
+
+            // Ctrl-D on an empty line is EOF, otherwise it deletes.
+            if key == Key::Ctrl('d') && self.buf.is_empty() {
+                write!(self.out, "\r\n")?;
+                self.out.flush()?;
+                return Ok(None);
+            }
+
+            if key == Key::Ctrl('c') {
+                // leave what was typed on screen, like other shells do.
+                self.hint.clear();
+                self.refresh_line()?;
+                self.move_to_end()?;
+                write!(self.out, "^C\r\n")?;
+                self.out.flush()?;
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+
+            self.handle_key(key)?;
+            self.refresh()?;
+        }
+    }
+
+    fn line(&self) -> String {
+        self.buf.iter().collect()
+    }
+
+    fn handle_key(&mut self, key: Key) -> io::Result<()> {
+        if let Some(state) = &mut self.vi {
+            match (state.mode, key) {
+                (Mode::Normal, _) => return self.handle_vi_key(key),
+                (Mode::Insert, Key::Escape) => {
+                    self.enter_normal_mode();
+                    return Ok(());
+                },
+                // escape followed quickly by a key looks like alt, but
+                // in insert mode it's much more likely to be a command.
+                (Mode::Insert, Key::Alt(c)) => {
+                    self.enter_normal_mode();
+                    return self.handle_vi_key(Key::Char(c));
+                },
+                (Mode::Insert, _) => {
+                    if let Some(recording) = &mut state.recording {
+                        recording.push(key);
+                    }
+                },
+            }
+        }
+
+        match key {
+            Key::Char(c) => self.insert(&[c]),
+            Key::Tab => self.complete()?,
+
+            Key::Backspace | Key::Ctrl('h') if self.pos > 0 => {
+                self.pos -= 1;
+                self.buf.remove(self.pos);
+            },
+            Key::Delete | Key::Ctrl('d') if self.pos < self.buf.len() => {
+                self.buf.remove(self.pos);
+            },
+
+            Key::Right | Key::Ctrl('f') | Key::End | Key::Ctrl('e') if self.showing_hint() => {
+                let hint = std::mem::take(&mut self.hint);
+                self.insert(&hint);
+            },
+            Key::Alt('f') if self.showing_hint() => {
+                // accept the suggestion up to the end of its next word.
+                let mut end = 0;
+                while end < self.hint.len() && !is_word_char(self.hint[end]) {
+                    end += 1;
+                }
+                while end < self.hint.len() && is_word_char(self.hint[end]) {
+                    end += 1;
+                }
+                let word: Vec<char> = self.hint.drain(..end).collect();
+                self.insert(&word);
+            },
+
+            Key::Left | Key::Ctrl('b') => self.pos = self.pos.saturating_sub(1),
+            Key::Right | Key::Ctrl('f') => self.pos = (self.pos + 1).min(self.buf.len()),
+            Key::Home | Key::Ctrl('a') => self.pos = 0,
+            Key::End | Key::Ctrl('e') => self.pos = self.buf.len(),
+            Key::Alt('b') => self.pos = self.word_start_before(self.pos),
+            Key::Alt('f') => self.pos = self.word_end_after(self.pos),
+
+            Key::Ctrl('k') => self.kill(self.pos, self.buf.len()),
+            Key::Ctrl('u') => self.kill(0, self.pos),
+            Key::Ctrl('w') | Key::Alt('\x7f') => {
+                // ctrl-w kills back to the previous whitespace.
+                let mut start = self.pos;
+                while start > 0 && self.buf[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !self.buf[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                self.kill(start, self.pos);
+            },
+            Key::Alt('d') => self.kill(self.pos, self.word_end_after(self.pos)),
+            Key::Ctrl('y') => {
+                let yanked = self.kill_ring.clone();
+                self.insert(&yanked);
+            },
+            Key::Ctrl('t') if self.pos > 0 && self.buf.len() > 1 => {
+                // swap the characters around the cursor, like readline.
+                if self.pos == self.buf.len() {
+                    self.pos -= 1;
+                }
+                self.buf.swap(self.pos - 1, self.pos);
+                self.pos += 1;
+            },
+
+            Key::Up | Key::Ctrl('p') => self.history_move(-1),
+            Key::Down | Key::Ctrl('n') => self.history_move(1),
+
+            Key::Ctrl('l') => {
+                write!(self.out, "\x1b[2J\x1b[H")?;
+                self.cursor_row = 0;
+            },
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn enter_normal_mode(&mut self) {
+        let state = match &mut self.vi {
+            Some(state) => state,
+            None => return,
+        };
+
+        state.mode = Mode::Normal;
+        // the change that got us into insert mode is done now.
+        if let Some(mut recording) = state.recording.take() {
+            recording.push(Key::Escape);
+            state.last_change = recording;
+        }
+
+        // like vi, leaving insert mode steps back onto the last character.
+        self.pos = self.pos.saturating_sub(1);
+        self.clamp_normal_cursor();
+    }
+
+    /// in normal mode the cursor sits on a character, never past the end.
+    fn clamp_normal_cursor(&mut self) {
+        if self.pos >= self.buf.len() {
+            self.pos = self.buf.len().saturating_sub(1);
+        }
+    }
+
+    fn handle_vi_key(&mut self, key: Key) -> io::Result<()> {
+        let state = match &mut self.vi {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+
+        state.pending.push(key);
+        match vi::parse(&state.pending) {
+            vi::Parse::Incomplete => Ok(()),
+            vi::Parse::Invalid => {
+                state.pending.clear();
+                write!(self.out, "\x07")
+            },
+            vi::Parse::Done(command) => {
+                let keys = std::mem::take(&mut state.pending);
+                if command.action.is_change() && !state.replaying {
+                    state.recording = Some(keys);
+                }
+                self.run_vi_command(command)
+            }
+        }
+    }
+
+    fn run_vi_command(&mut self, command: vi::Command) -> io::Result<()> {
+        let count = command.count;
+
+        match command.action {
+            Action::Move(motion) => match self.vi_motion(motion, count) {
+                Some(target) => self.pos = target,
+                None => write!(self.out, "\x07")?,
+            },
+            Action::Apply(op, target) => self.vi_apply(op, target, count)?,
+            Action::Insert(at) => {
+                self.save_undo();
+                self.pos = match at {
+                    InsertAt::Cursor => self.pos,
+                    InsertAt::AfterCursor => (self.pos + 1).min(self.buf.len()),
+                    InsertAt::LineStart => {
+                        vi::motion_target(&self.buf, self.pos, Motion::FirstNonBlank, 1, None).unwrap_or(0)
+                    },
+                    InsertAt::LineEnd => self.buf.len(),
+                };
+                self.set_vi_mode(Mode::Insert);
+            },
+            Action::Replace(c) => {
+                if self.pos.saturating_add(count) <= self.buf.len() {
+                    self.save_undo();
+                    for i in self.pos..self.pos + count {
+                        self.buf[i] = c;
+                    }
+                    self.pos += count - 1;
+                }
+            },
+            Action::Put { before } => {
+                if !self.kill_ring.is_empty() {
+                    self.save_undo();
+                    if !before && !self.buf.is_empty() {
+                        self.pos += 1;
+                    }
+                    for _ in 0..count {
+                        let text = self.kill_ring.clone();
+                        self.insert(&text);
+                    }
+                    // the cursor ends up on the last character put.
+                    self.pos = self.pos.saturating_sub(1);
+                }
+            },
+            Action::Undo => {
+                let previous = self.vi.as_mut().and_then(|state| state.undo.pop());
+                match previous {
+                    Some((buf, pos)) => {
+                        self.buf = buf;
+                        self.pos = pos;
+                    },
+                    None => write!(self.out, "\x07")?,
+                }
+            },
+            Action::Repeat => {
+                let keys = match &self.vi {
+                    Some(state) => state.last_change.clone(),
+                    None => Vec::new(),
+                };
+                self.set_replaying(true);
+                for _ in 0..count {
+                    for key in &keys {
+                        self.handle_key(*key)?;
+                    }
+                }
+                self.set_replaying(false);
+            },
+            Action::HistoryPrev => self.history_move(-1),
+            Action::HistoryNext => self.history_move(1),
+        }
+
+        if self.vi.as_ref().is_some_and(|state| state.mode == Mode::Normal) {
+            // anything that didn't leave us in insert mode is finished.
+            if let Some(state) = &mut self.vi {
+                if let Some(recording) = state.recording.take() {
+                    state.last_change = recording;
+                }
+            }
+            self.clamp_normal_cursor();
+        }
+
+        Ok(())
+    }
+
+    fn vi_motion(&mut self, motion: Motion, count: usize) -> Option<usize> {
+        let state = self.vi.as_mut()?;
+
+        if let Motion::Find { ch, forward, till } = motion {
+            state.last_find = Some((ch, forward, till));
+        }
+
+        vi::motion_target(&self.buf, self.pos, motion, count, state.last_find)
+    }
+
+    fn vi_apply(&mut self, op: Operator, target: Target, count: usize) -> io::Result<()> {
+        let (start, end) = match target {
+            Target::Line => (0, self.buf.len()),
+            Target::Motion(motion) => {
+                // `cw` on a word behaves like `ce`, it leaves the space alone.
+                let (motion, target) = match motion {
+                    Motion::WordForward { big }
+                        if op == Operator::Change
+                            && self.buf.get(self.pos).is_some_and(|c| !c.is_whitespace()) =>
+                    {
+                        (Motion::WordEnd { big }, Some(vi::change_word_end(&self.buf, self.pos, big, count)))
+                    },
+                    other => (other, self.vi_motion(other, count)),
+                };
+
+                let target = match target {
+                    Some(target) => target,
+                    None => return write!(self.out, "\x07"),
+                };
+
+                let (start, mut end) = if target < self.pos {
+                    (target, self.pos)
+                }
+                else {
+                    (self.pos, target)
+                };
+                if motion.is_inclusive() {
+                    end += 1;
+                }
+                (start, end.min(self.buf.len()))
+            },
+        };
+
+        if op != Operator::Yank {
+            self.save_undo();
+        }
+
+        self.kill_ring = self.buf[start..end].to_vec();
+        if op != Operator::Yank {
+            self.buf.drain(start..end);
+        }
+        self.pos = start;
+
+        if op == Operator::Change {
+            self.set_vi_mode(Mode::Insert);
+        }
+
+        Ok(())
+    }
+
+    fn save_undo(&mut self) {
+        if let Some(state) = &mut self.vi {
+            state.undo.push((self.buf.clone(), self.pos));
+        }
+    }
+
+    fn set_vi_mode(&mut self, mode: Mode) {
+        if let Some(state) = &mut self.vi {
+            state.mode = mode;
+        }
+    }
+
+    fn set_replaying(&mut self, replaying: bool) {
+        if let Some(state) = &mut self.vi {
+            state.replaying = replaying;
+        }
+    }
+
+    /// the prompt, with the vi mode in front of it when there is one.
+    fn full_prompt(&self) -> String {
+        match &self.vi {
+            Some(state) if state.mode == Mode::Normal => format!("[N] {}", self.prompt),
+            Some(_) => format!("[I] {}", self.prompt),
+            None => self.prompt.to_string(),
+        }
+    }
+
+    fn insert(&mut self, chars: &[char]) {
+        for (i, c) in chars.iter().enumerate() {
+            self.buf.insert(self.pos + i, *c);
+        }
+        self.pos += chars.len();
+    }
+
+    fn kill(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        self.kill_ring = self.buf.drain(start..end).collect();
+        self.pos = start;
+    }
+
+    fn word_start_before(&self, mut pos: usize) -> usize {
+        while pos > 0 && !is_word_char(self.buf[pos - 1]) {
+            pos -= 1;
+        }
+        while pos > 0 && is_word_char(self.buf[pos - 1]) {
+            pos -= 1;
+        }
+        pos
+    }
+
+    fn word_end_after(&self, mut pos: usize) -> usize {
+        while pos < self.buf.len() && !is_word_char(self.buf[pos]) {
+            pos += 1;
+        }
+        while pos < self.buf.len() && is_word_char(self.buf[pos]) {
+            pos += 1;
+        }
+        pos
+    }
+
+    fn history_move(&mut self, direction: isize) {
+        let target = self.hist_index as isize + direction;
+        if target < 0 || target > self.history.len() as isize {
+            return;
+        }
+
+        if self.hist_index == self.history.len() {
+            self.saved = self.buf.clone();
+        }
+
+        self.hist_index = target as usize;
+        self.buf = match self.history.get(self.hist_index) {
+            Some(entry) => entry.line.chars().collect(),
+            None => self.saved.clone(),
+        };
+        self.pos = self.buf.len();
+    }
+
+    fn complete(&mut self) -> io::Result<()> {
+        let line = self.line();
+        let (start, candidates) = self.helper.complete(&line, self.pos);
+        let start = start.min(self.pos);
+
+        if candidates.is_empty() {
+            write!(self.out, "\x07")?;
+            return Ok(());
+        }
+
+        if candidates.len() == 1 {
+            let mut value: Vec<char> = candidates[0].value.chars().collect();
+            if !candidates[0].value.ends_with('/') {
+                value.push(' ');
+            }
+            self.buf.splice(start..self.pos, value.iter().copied());
+            self.pos = start + value.len();
+            return Ok(());
+        }
+
+        let prefix = common_prefix(&candidates);
+        if prefix.len() > self.pos - start {
+            self.buf.splice(start..self.pos, prefix.iter().copied());
+            self.pos = start + prefix.len();
+            return Ok(());
+        }
+
+        self.list_candidates(&candidates)
+    }
+
+    fn list_candidates(&mut self, candidates: &[Completion]) -> io::Result<()> {
+        self.move_to_end()?;
+        write!(self.out, "\r\n")?;
+
+        let widest = candidates.iter().map(|c| c.value.chars().count()).max().unwrap_or(0);
+
+        if candidates.iter().any(|c| c.desc.is_some()) {
+            for c in candidates {
+                match &c.desc {
+                    Some(desc) => write!(self.out, "{:<widest$}  -- {}\r\n", c.value, desc)?,
+                    None => write!(self.out, "{}\r\n", c.value)?,
+                }
+            }
+        }
+        else {
+            let column = widest + 2;
+            let per_row = (terminal_width() / column).max(1);
+            for row in candidates.chunks(per_row) {
+                for c in row {
+                    write!(self.out, "{:<column$}", c.value)?;
+                }
+                write!(self.out, "\r\n")?;
+            }
+        }
+
+        self.cursor_row = 0;
+        Ok(())
+    }
+
+    fn showing_hint(&self) -> bool {
+        !self.hint.is_empty() && self.pos == self.buf.len()
+    }
+
+    fn move_to_end(&mut self) -> io::Result<()> {
+        let width = terminal_width();
+        let end = visible_width(&self.full_prompt()) + self.buf.len() + self.hint.len();
+        let end_row = end / width;
+        if end_row > self.cursor_row {
+            write!(self.out, "\x1b[{}B", end_row - self.cursor_row)?;
+        }
+        self.cursor_row = end_row;
+        Ok(())
+    }
+
+    fn refresh(&mut self) -> io::Result<()> {
+        // suggestions only make sense when typing at the end of the line.
+        self.hint = if self.pos == self.buf.len() {
+            match self.helper.hint(&self.line()) {
+                Some(hint) => hint.chars().collect(),
+                None => Vec::new(),
+            }
+        }
+        else {
+            Vec::new()
+        };
+
+        self.refresh_line()
+    }
+
+    /// redraw the prompt and the line, without asking for a new suggestion.
+    fn refresh_line(&mut self) -> io::Result<()> {
+        let width = terminal_width();
+        let mut frame = String::new();
+
+        // go back to where the prompt starts and clear everything after it.
+        if self.cursor_row > 0 {
+            frame.push_str(&format!("\x1b[{}A", self.cursor_row));
+        }
+        frame.push_str("\r\x1b[J");
+
+        let prompt = self.full_prompt();
+        frame.push_str(&prompt);
+        frame.push_str(&self.helper.highlight(&self.line()));
+        if !self.hint.is_empty() {
+            let hint: String = self.hint.iter().collect();
+            frame.push_str(&self.helper.highlight_hint(&hint));
+        }
+
+        let prompt_width = visible_width(&prompt);
+        let end = prompt_width + self.buf.len() + self.hint.len();
+        let cursor = prompt_width + self.pos;
+
+        // most terminals wait for another character before wrapping, so
+        // force the wrap when the line exactly fills the last row.
+        if end > 0 && end.is_multiple_of(width) {
+            frame.push_str("\r\n");
+        }
+
+        let end_row = end / width;
+        let cursor_row = cursor / width;
+        if end_row > cursor_row {
+            frame.push_str(&format!("\x1b[{}A", end_row - cursor_row));
+        }
+        frame.push('\r');
+        if !cursor.is_multiple_of(width) {
+            frame.push_str(&format!("\x1b[{}C", cursor % width));
+        }
+
+        self.cursor_row = cursor_row;
+        self.out.write_all(frame.as_bytes())?;
+        self.out.flush()
+    }
+}
+
+fn common_prefix(candidates: &[Completion]) -> Vec<char> {
+    let mut prefix: Vec<char> = match candidates.first() {
+        Some(c) => c.value.chars().collect(),
+        None => return Vec::new(),
+    };
+
+    for c in &candidates[1..] {
+        let matching = prefix
+            .iter()
+            .zip(c.value.chars())
+            .take_while(|(a, b)| **a == *b)
+            .count();
+        prefix.truncate(matching);
+    }
+
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// a helper that knows nothing.
+    struct Plain;
+
+    impl Helper for Plain {
+        fn complete(&self, _line: &str, pos: usize) -> (usize, Vec<Completion>) {
+            (pos, Vec::new())
+        }
+
+        fn highlight(&self, line: &str) -> String {
+            line.to_string()
+        }
+
+        fn hint(&self, _line: &str) -> Option<String> {
+            None
+        }
+
+        fn highlight_hint(&self, hint: &str) -> String {
+            hint.to_string()
+        }
+    }
+
+    /// what the editor wrote, shared so it can be looked at afterwards.
+    #[derive(Clone, Default)]
+    struct Screen(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Screen {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Screen {
+        fn rang(&self) -> bool {
+            self.0.borrow().contains(&b'\x07')
+        }
+    }
+
+    /// `\x1b` is escape, everything else is typed as is.
+    fn keys(typed: &str) -> Vec<Key> {
+        typed.chars().map(|c| if c == '\x1b' { Key::Escape } else { Key::Char(c) }).collect()
+    }
+
+    fn editor<'a>(helper: &'a dyn Helper, history: &'a [HistoryEntry], screen: &Screen) -> LineEditor<'a> {
+        let mut editor = LineEditor::new("> ", history, helper);
+        editor.out = Box::new(screen.clone());
+        editor
+    }
+
+    /// press each key, redrawing after each one like `read_line` does.
+    fn press(editor: &mut LineEditor<'_>, typed: &[Key]) {
+        for key in typed {
+            editor.handle_key(*key).unwrap();
+            editor.refresh().unwrap();
+        }
+    }
+
+    /// type `typed` into a vi mode editor, returning the line and where
+    /// the cursor ended up.
+    fn vi(typed: &str) -> (String, usize) {
+        let screen = Screen::default();
+        let mut editor = editor(&Plain, &[], &screen).vi_mode(true);
+        press(&mut editor, &keys(typed));
+        (editor.line(), editor.pos)
+    }
+
+    fn vi_mode(typed: &str) -> Mode {
+        let screen = Screen::default();
+        let mut editor = editor(&Plain, &[], &screen).vi_mode(true);
+        press(&mut editor, &keys(typed));
+        editor.vi.as_ref().unwrap().mode
+    }
+
+    #[test]
+    fn escape_steps_back_onto_the_line() {
+        assert_eq!(vi("hello"), ("hello".to_string(), 5));
+        assert_eq!(vi("hello\x1b"), ("hello".to_string(), 4));
+        assert_eq!(vi("\x1b"), (String::new(), 0));
+    }
+
+    #[test]
+    fn switching_modes() {
+        assert_eq!(vi_mode("ab"), Mode::Insert);
+        assert_eq!(vi_mode("ab\x1b"), Mode::Normal);
+        assert_eq!(vi_mode("ab\x1bi"), Mode::Insert);
+        assert_eq!(vi_mode("ab\x1bcw"), Mode::Insert);
+        assert_eq!(vi_mode("ab\x1bdw"), Mode::Normal);
+
+        assert_eq!(vi("bc\x1b0ia\x1b"), ("abc".to_string(), 0));
+        assert_eq!(vi("ac\x1b0ab\x1b"), ("abc".to_string(), 1));
+        assert_eq!(vi("  b\x1b0Ia\x1b"), ("  ab".to_string(), 2));
+        assert_eq!(vi("a\x1b0Abc"), ("abc".to_string(), 3));
+
+        let screen = Screen::default();
+        let mut editor = editor(&Plain, &[], &screen).vi_mode(true);
+        assert_eq!(editor.full_prompt(), "[I] > ");
+        press(&mut editor, &[Key::Escape]);
+        assert_eq!(editor.full_prompt(), "[N] > ");
+    }
+
+    #[test]
+    fn motions() {
+        assert_eq!(vi("one two three\x1b0w"), ("one two three".to_string(), 4));
+        assert_eq!(vi("one two three\x1b02w"), ("one two three".to_string(), 8));
+        assert_eq!(vi("one two three\x1bb"), ("one two three".to_string(), 8));
+        assert_eq!(vi("one two three\x1b0e"), ("one two three".to_string(), 2));
+        assert_eq!(vi("one two three\x1b0$"), ("one two three".to_string(), 12));
+        assert_eq!(vi("  x\x1b^"), ("  x".to_string(), 2));
+        assert_eq!(vi("a-b-c\x1b0f-"), ("a-b-c".to_string(), 1));
+        assert_eq!(vi("a-b-c\x1b0f-;"), ("a-b-c".to_string(), 3));
+        assert_eq!(vi("ab-c\x1b0t-"), ("ab-c".to_string(), 1));
+        // `t` skips a match right next to the cursor, so `;` moves on.
+        assert_eq!(vi("a-b-c\x1b0t-"), ("a-b-c".to_string(), 2));
+        // a motion that goes nowhere rings the bell.
+        let screen = Screen::default();
+        let mut editor = editor(&Plain, &[], &screen).vi_mode(true);
+        press(&mut editor, &keys("abc\x1b0fz"));
+        assert_eq!(editor.pos, 0);
+        assert!(screen.rang());
+    }
+
+    #[test]
+    fn operators_with_motions() {
+        assert_eq!(vi("one two three\x1b0dw"), ("two three".to_string(), 0));
+        assert_eq!(vi("one two three\x1b0d2w"), ("three".to_string(), 0));
+        assert_eq!(vi("one two three\x1b02dw"), ("three".to_string(), 0));
+        assert_eq!(vi("one two three\x1b0de"), (" two three".to_string(), 0));
+        assert_eq!(vi("one two three\x1b0wD"), ("one ".to_string(), 3));
+        assert_eq!(vi("one two three\x1bdb"), ("one two e".to_string(), 8));
+        assert_eq!(vi("a-b-c\x1b0df-"), ("b-c".to_string(), 0));
+        assert_eq!(vi("one two\x1bdd"), (String::new(), 0));
+        assert_eq!(vi("abcd\x1b0x"), ("bcd".to_string(), 0));
+        assert_eq!(vi("abcd\x1b03x"), ("d".to_string(), 0));
+        assert_eq!(vi("abcd\x1bX"), ("abd".to_string(), 2));
+    }
+
+    #[test]
+    fn change_leaves_the_space_alone() {
+        assert_eq!(vi("one two three\x1b0wcwTWO\x1b"), ("one TWO three".to_string(), 6));
+        assert_eq!(vi("one two\x1b0cc"), (String::new(), 0));
+        assert_eq!(vi("one two\x1b0wCx\x1b"), ("one x".to_string(), 4));
+        assert_eq!(vi("abc\x1b0sX\x1b"), ("Xbc".to_string(), 0));
+    }
+
+    #[test]
+    fn yank_and_put() {
+        assert_eq!(vi("ab\x1b0ylp"), ("aab".to_string(), 1));
+        assert_eq!(vi("ab\x1b0ylP"), ("aab".to_string(), 0));
+        assert_eq!(vi("one two\x1b0ywP"), ("one one two".to_string(), 3));
+        assert_eq!(vi("ab\x1b0yl3p"), ("aaaab".to_string(), 3));
+        // deleting fills the kill ring too.
+        assert_eq!(vi("abc\x1b0xp"), ("bac".to_string(), 1));
+        // yanking doesn't change the line or move.
+        assert_eq!(vi("one two\x1b0wyb"), ("one two".to_string(), 0));
+    }
+
+    #[test]
+    fn replace() {
+        assert_eq!(vi("abc\x1b0rX"), ("Xbc".to_string(), 0));
+        assert_eq!(vi("abc\x1b02rX"), ("XXc".to_string(), 1));
+        // not enough characters, nothing happens.
+        assert_eq!(vi("abc\x1b05rX"), ("abc".to_string(), 0));
+        assert_eq!(vi("abc\x1b099999999rX"), ("abc".to_string(), 0));
+    }
+
+    #[test]
+    fn undo() {
+        assert_eq!(vi("abc\x1bxu"), ("abc".to_string(), 2));
+        assert_eq!(vi("abc\x1bxxuu"), ("abc".to_string(), 2));
+        assert_eq!(vi("one two\x1b0cwuno\x1bu"), ("one two".to_string(), 0));
+        // yanks and moves aren't undone.
+        assert_eq!(vi("abc\x1bx0ylwu"), ("abc".to_string(), 2));
+
+        let screen = Screen::default();
+        let mut editor = editor(&Plain, &[], &screen).vi_mode(true);
+        press(&mut editor, &keys("abc\x1bu"));
+        assert!(screen.rang(), "nothing to undo");
+    }
+
+    #[test]
+    fn repeat() {
+        assert_eq!(vi("a b c d\x1b0dw."), ("c d".to_string(), 0));
+        assert_eq!(vi("a b c d\x1b0dw2."), ("d".to_string(), 0));
+        assert_eq!(vi("abcdef\x1b0x.."), ("def".to_string(), 0));
+        // a change repeats with the text that was typed.
+        assert_eq!(vi("a b\x1b0cwx\x1bw."), ("x x".to_string(), 2));
+        assert_eq!(vi("b\x1b0ia\x1b."), ("aab".to_string(), 0));
+        // moves aren't changes.
+        assert_eq!(vi("abc\x1b0xl."), ("b".to_string(), 0));
+        // and repeating doesn't undo twice.
+        assert_eq!(vi("abc\x1b0x.u"), ("bc".to_string(), 0));
+    }
+
+    #[test]
+    fn invalid_commands_ring_the_bell() {
+        let screen = Screen::default();
+        let mut editor = editor(&Plain, &[], &screen).vi_mode(true);
+        press(&mut editor, &keys("abc\x1bdZ"));
+        assert_eq!(editor.line(), "abc");
+        assert!(screen.rang());
+        // and are forgotten.
+        press(&mut editor, &keys("0x"));
+        assert_eq!(editor.line(), "bc");
+    }
+
+    /// completes the last word to whichever of `words` it starts, and
+    /// hints the rest of the first of `lines` that starts with the line.
+    struct Canned {
+        words: &'static [&'static str],
+        lines: &'static [&'static str],
+    }
+
+    impl Helper for Canned {
+        fn complete(&self, line: &str, pos: usize) -> (usize, Vec<Completion>) {
+            let before: String = line.chars().take(pos).collect();
+            let start = before.rfind(' ').map_or(0, |i| i + 1);
+            let candidates = self.words
+                .iter()
+                .filter(|word| word.starts_with(&before[start..]))
+                .map(|word| Completion::new(*word))
+                .collect();
+            (before[..start].chars().count(), candidates)
+        }
+
+        fn highlight(&self, line: &str) -> String {
+            line.to_string()
+        }
+
+        fn hint(&self, line: &str) -> Option<String> {
+            let found = self.lines.iter().find(|found| found.starts_with(line) && found.len() > line.len())?;
+            Some(found[line.len()..].to_string())
+        }
+
+        fn highlight_hint(&self, hint: &str) -> String {
+            hint.to_string()
+        }
+    }
+
+    const FILES: Canned = Canned {
+        words: &["alpha", "alpine", "beta/", "\"My Dir\"/", "\"My Docs\"/"],
+        lines: &[],
+    };
+
+    fn completions(values: &[&str]) -> Vec<Completion> {
+        values.iter().map(|value| Completion::new(*value)).collect()
+    }
+
+    #[test]
+    fn common_prefix_of_candidates() {
+        assert_eq!(common_prefix(&[]), Vec::<char>::new());
+        assert_eq!(common_prefix(&completions(&["alone"])), "alone".chars().collect::<Vec<_>>());
+        assert_eq!(common_prefix(&completions(&["alpha", "alpine", "also"])), vec!['a', 'l']);
+        assert_eq!(common_prefix(&completions(&["alpha", "beta"])), Vec::<char>::new());
+        assert_eq!(common_prefix(&completions(&["éa", "éb"])), vec!['é']);
+    }
+
+    /// type `typed`, then press tab.
+    fn tab(helper: &dyn Helper, typed: &str) -> (String, usize, Screen) {
+        let screen = Screen::default();
+        let mut editor = editor(helper, &[], &screen);
+        press(&mut editor, &keys(typed));
+        press(&mut editor, &[Key::Tab]);
+        (editor.line(), editor.pos, screen)
+    }
+
+    #[test]
+    fn one_candidate_is_filled_in() {
+        let (line, pos, _) = tab(&FILES, "cat alpi");
+        assert_eq!((line.as_str(), pos), ("cat alpine ", 11));
+        // directories don't get a space, so you can keep going.
+        let (line, pos, _) = tab(&FILES, "cd b");
+        assert_eq!((line.as_str(), pos), ("cd beta/", 8));
+    }
+
+    #[test]
+    fn several_candidates_fill_in_what_they_share() {
+        let (line, pos, screen) = tab(&FILES, "cat a");
+        assert_eq!((line.as_str(), pos), ("cat alp", 7));
+        let shown = String::from_utf8(screen.0.borrow().clone()).unwrap();
+        assert!(!shown.contains("alpha"), "nothing is listed: {:?}", shown);
+
+        let (line, _, _) = tab(&FILES, "cd \"M");
+        assert_eq!(line, "cd \"My D");
+    }
+
+    #[test]
+    fn candidates_are_listed_once_nothing_more_is_shared() {
+        let (line, pos, screen) = tab(&FILES, "cat alp");
+        assert_eq!((line.as_str(), pos), ("cat alp", 7));
+        let shown = String::from_utf8(screen.0.borrow().clone()).unwrap();
+        assert!(shown.contains("alpha") && shown.contains("alpine"), "{:?}", shown);
+        assert!(!shown.contains("beta"));
+    }
+
+    #[test]
+    fn no_candidates_ring_the_bell() {
+        let (line, _, screen) = tab(&FILES, "cat zz");
+        assert_eq!(line, "cat zz");
+        assert!(screen.rang());
+    }
+
+    #[test]
+    fn completing_in_the_middle_of_the_line() {
+        let screen = Screen::default();
+        let mut editor = editor(&FILES, &[], &screen);
+        press(&mut editor, &keys("cat alpi x"));
+        press(&mut editor, &[Key::Left, Key::Left, Key::Tab]);
+        assert_eq!(editor.line(), "cat alpine  x");
+        assert_eq!(editor.pos, 11);
+    }
+
+    const PAST: Canned = Canned {
+        words: &[],
+        lines: &["git commit -m wip"],
+    };
+
+    /// type `typed`, then press `then`.
+    fn hinted(typed: &str, then: &[Key]) -> (String, usize, String) {
+        let screen = Screen::default();
+        let mut editor = editor(&PAST, &[], &screen);
+        press(&mut editor, &keys(typed));
+        press(&mut editor, then);
+        let hint: String = editor.hint.iter().collect();
+        (editor.line(), editor.pos, hint)
+    }
+
+    #[test]
+    fn the_hint_follows_the_line() {
+        assert_eq!(hinted("git", &[]).2, " commit -m wip");
+        assert_eq!(hinted("git co", &[]).2, "mmit -m wip");
+        assert_eq!(hinted("gx", &[]).2, "");
+        assert_eq!(hinted("git commit -m wip", &[]).2, "");
+    }
+
+    #[test]
+    fn the_whole_hint_is_accepted_at_the_end() {
+        for key in [Key::Right, Key::End, Key::Ctrl('f'), Key::Ctrl('e')] {
+            let (line, pos, hint) = hinted("git", &[key]);
+            assert_eq!((line.as_str(), pos, hint.as_str()), ("git commit -m wip", 17, ""), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn alt_f_accepts_one_word_of_the_hint() {
+        let (line, pos, hint) = hinted("git", &[Key::Alt('f')]);
+        assert_eq!((line.as_str(), pos, hint.as_str()), ("git commit", 10, " -m wip"));
+        let (line, _, hint) = hinted("git", &[Key::Alt('f'), Key::Alt('f')]);
+        assert_eq!((line.as_str(), hint.as_str()), ("git commit -m", " wip"));
+    }
+
+    #[test]
+    fn no_hint_away_from_the_end() {
+        let (line, pos, hint) = hinted("git", &[Key::Left]);
+        assert_eq!((line.as_str(), pos, hint.as_str()), ("git", 2, ""));
+        // so moving right only moves the cursor.
+        let (line, pos, _) = hinted("git", &[Key::Left, Key::Right]);
+        assert_eq!((line.as_str(), pos), ("git", 3));
+        let (line, pos, _) = hinted("git", &[Key::Home, Key::End]);
+        assert_eq!((line.as_str(), pos), ("git", 3));
+    }
+}
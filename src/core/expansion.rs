@@ -0,0 +1,284 @@
+// History expansion, the `!!` and `^old^new` family from csh/bash.
+//
+// Supported events:
+//   !!         the previous line
+//   !n         line number n (as shown by `history`)
+//   !-n        the line n lines back
+//   !prefix    the most recent line starting with prefix
+// each of which can be followed by a word designator (`:$`, `:^`, `:*`
+// or `:n`). `!$`, `!^` and `!*` are short for `!!:$`, `!!:^` and `!!:*`.
+//
+// `^old^new` at the start of the line re-runs the previous line with the
+// first `old` replaced by `new`.
+
+use crate::error::UniError;
+use super::{input::HistoryEntry, lexer::{tokenize, TokenKind}};
+
+type ExpandResult<T> = Result<T, UniError>;
+
+/// expand `line` against `history`. returns `None` when there was
+/// nothing to expand, so callers know when to echo the new line.
+pub fn expand_history(line: &str, history: &[HistoryEntry]) -> ExpandResult<Option<String>> {
+    if let Some(rest) = line.strip_prefix('^') {
+        return quick_substitution(rest, history).map(Some);
+    }
+
+    if !line.contains('!') {
+        return Ok(None);
+    }
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut result = String::new();
+    let mut expanded = false;
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '\'' && !in_double_quote {
+            in_single_quote = !in_single_quote;
+        }
+        if c == '"' && !in_single_quote {
+            in_double_quote = !in_double_quote;
+        }
+
+        // the backslash has done its job, so dropping it is a change
+        // even when nothing else expands.
+        if c == '\\' && chars.get(i + 1) == Some(&'!') {
+            result.push('!');
+            expanded = true;
+            i += 2;
+            continue;
+        }
+
+        // an escaped quote doesn't open or close a string.
+        if c == '\\' && in_double_quote && chars.get(i + 1) == Some(&'"') {
+            result.push_str("\\\"");
+            i += 2;
+            continue;
+        }
+
+        if c != '!' || in_single_quote {
+            result.push(c);
+            i += 1;
+            continue;
+        }
+
+        // a lone `!` (or one followed by something that can't start an
+        // event, like the quote closing `"hi!"`) is left alone.
+        match chars.get(i + 1) {
+            None => {
+                result.push(c);
+                i += 1;
+                continue;
+            },
+            Some(next) if next.is_whitespace() || matches!(next, '=' | '(' | '"') => {
+                result.push(c);
+                i += 1;
+                continue;
+            },
+            _ => (),
+        }
+
+        let (text, consumed) = expand_event(&chars[i + 1..], history)?;
+        result.push_str(&text);
+        expanded = true;
+        i += 1 + consumed;
+    }
+
+    Ok(if expanded { Some(result) } else { None })
+}
+
+/// expand a single event, `chars` starts just after the `!`.
+/// returns the expanded text and how many characters were used.
+fn expand_event(chars: &[char], history: &[HistoryEntry]) -> ExpandResult<(String, usize)> {
+    let (entry, used) = match chars[0] {
+        '!' => (nth_last(history, 1, "!!")?, 1),
+        // shorthand for a word of the previous line.
+        '$' | '^' | '*' => {
+            let entry = nth_last(history, 1, "!!")?;
+            let text = select_words(&entry.line, &chars[0].to_string())?;
+            return Ok((text, 1));
+        },
+        '-' => {
+            let digits = take_while(&chars[1..], |c| c.is_ascii_digit());
+            let n: usize = digits.parse().map_err(|_| {
+                UniError::Custom(format!("!-{}: bad event specification", digits))
+            })?;
+            (nth_last(history, n, &format!("!-{}", digits))?, 1 + digits.len())
+        },
+        c if c.is_ascii_digit() => {
+            let digits = take_while(chars, |c| c.is_ascii_digit());
+            let n: usize = digits.parse().unwrap_or(0);
+            match n.checked_sub(1).and_then(|index| history.get(index)) {
+                Some(entry) => (entry, digits.len()),
+                None => return Err(event_not_found(&format!("!{}", digits))),
+            }
+        },
+        _ => {
+            let prefix = take_while(chars, |c| !c.is_whitespace() && !matches!(c, ':' | '!' | '"'));
+            match history.iter().rev().find(|entry| entry.line.starts_with(&prefix)) {
+                Some(entry) => (entry, prefix.len()),
+                None => return Err(event_not_found(&format!("!{}", prefix))),
+            }
+        }
+    };
+
+    // an optional word designator, like `!!:$` or `!ls:1`
+    if chars.get(used) == Some(&':') {
+        let designator = match chars.get(used + 1) {
+            Some(c @ ('$' | '^' | '*')) => c.to_string(),
+            Some(c) if c.is_ascii_digit() => take_while(&chars[used + 1..], |c| c.is_ascii_digit()),
+            _ => return Ok((entry.line.clone(), used)),
+        };
+        let text = select_words(&entry.line, &designator)?;
+        return Ok((text, used + 1 + designator.len()));
+    }
+
+    Ok((entry.line.clone(), used))
+}
+
+fn quick_substitution(rest: &str, history: &[HistoryEntry]) -> ExpandResult<String> {
+    let mut parts = rest.splitn(3, '^');
+    let old = parts.next().unwrap_or("");
+    let new = parts.next().unwrap_or("");
+    // anything after the closing `^` is tacked onto the end.
+    let trailing = parts.next().unwrap_or("");
+
+    let previous = nth_last(history, 1, "^")?;
+    if old.is_empty() || !previous.line.contains(old) {
+        return Err(UniError::Custom(format!("^{}^{}: substitution failed", old, new)));
+    }
+
+    Ok(format!("{}{}", previous.line.replacen(old, new, 1), trailing))
+}
+
+/// pick words out of `line` using a designator (`$`, `^`, `*` or a number).
+fn select_words(line: &str, designator: &str) -> ExpandResult<String> {
+    let words = split_words(line);
+    let bad_designator = || UniError::Custom(format!(":{}: bad word specifier", designator));
+
+    match designator {
+        "$" => words.last().cloned().ok_or_else(bad_designator),
+        "^" => words.get(1).cloned().ok_or_else(bad_designator),
+        "*" => Ok(words.get(1..).unwrap_or(&[]).join(" ")),
+        n => {
+            let n: usize = n.parse().map_err(|_| bad_designator())?;
+            words.get(n).cloned().ok_or_else(bad_designator)
+        }
+    }
+}
+
+/// split a line into words, keeping quoted strings in one piece.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+
+    for token in tokenize(line) {
+        if token.kind == TokenKind::Whitespace {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        current.push_str(token.text);
+    }
+
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+fn nth_last<'a>(history: &'a [HistoryEntry], n: usize, event: &str) -> ExpandResult<&'a HistoryEntry> {
+    match n.checked_sub(1).and_then(|n| history.iter().rev().nth(n)) {
+        Some(entry) => Ok(entry),
+        None => Err(event_not_found(event)),
+    }
+}
+
+fn take_while(chars: &[char], pred: impl Fn(char) -> bool) -> String {
+    chars.iter().take_while(|c| pred(**c)).collect()
+}
+
+fn event_not_found(event: &str) -> UniError {
+    UniError::NotFound(format!("{}: event not found", event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+
+    fn history(lines: &[&str]) -> Vec<HistoryEntry> {
+        lines.iter().map(|line| HistoryEntry {
+            line: line.to_string(),
+            cwd: "/".to_string(),
+            timestamp: Local::now(),
+            status: Some(0),
+            duration: None,
+        }).collect()
+    }
+
+    fn expand(line: &str) -> Option<String> {
+        let history = history(&["ls -l /tmp", "echo one two three"]);
+        expand_history(line, &history).unwrap()
+    }
+
+    #[test]
+    fn events() {
+        assert_eq!(expand("!!"), Some("echo one two three".to_string()));
+        assert_eq!(expand("!1"), Some("ls -l /tmp".to_string()));
+        assert_eq!(expand("!-2 | x"), Some("ls -l /tmp | x".to_string()));
+        assert_eq!(expand("sudo !ls"), Some("sudo ls -l /tmp".to_string()));
+    }
+
+    #[test]
+    fn word_designators() {
+        assert_eq!(expand("cd !$"), Some("cd three".to_string()));
+        assert_eq!(expand("!^"), Some("one".to_string()));
+        assert_eq!(expand("x !*"), Some("x one two three".to_string()));
+        assert_eq!(expand("!ls:2"), Some("/tmp".to_string()));
+    }
+
+    #[test]
+    fn quick_substitution_replaces_the_first_match() {
+        assert_eq!(expand("^two^2"), Some("echo one 2 three".to_string()));
+    }
+
+    #[test]
+    fn a_bang_that_isnt_an_event_is_left_alone() {
+        assert_eq!(expand("echo hi!"), None);
+        assert_eq!(expand("echo \"hi!\""), None);
+        assert_eq!(expand("echo \"hi! there\""), None);
+        assert_eq!(expand("[ ! -f x ]"), None);
+        assert_eq!(expand("x != y"), None);
+        assert_eq!(expand("!(x)"), None);
+        assert_eq!(expand("echo 'hi !!'"), None);
+    }
+
+    #[test]
+    fn quotes_dont_hide_events() {
+        assert_eq!(expand("echo \"!!\""), Some("echo \"echo one two three\"".to_string()));
+        assert_eq!(expand("echo \"it's !$\""), Some("echo \"it's three\"".to_string()));
+        assert_eq!(expand("echo \"say \\\"!$\\\"\""), Some("echo \"say \\\"three\\\"\"".to_string()));
+    }
+
+    #[test]
+    fn escaped_bangs_are_kept() {
+        assert_eq!(expand("echo \\!!!"), Some("echo !echo one two three".to_string()));
+        assert_eq!(expand("echo \\!foo"), Some("echo !foo".to_string()));
+        assert_eq!(expand("echo \\!\\!"), Some("echo !!".to_string()));
+        assert_eq!(expand("echo a\\b!"), None, "only backslashes before a bang go");
+    }
+
+    #[test]
+    fn missing_events_fail() {
+        let e = expand_history("!nope", &history(&["ls"])).unwrap_err();
+        assert!(matches!(e.kind(), UniError::NotFound(_)));
+        assert!(expand_history("!!", &[]).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+// Syntax highlighting for the line being edited.
+
+use super::lexer::{tokenize, TokenKind};
+use super::settings::Settings;
+
+/// color `line` using the highlight colors in `settings`.
+///
+/// `is_command` decides whether a word in command position resolves
+/// to something we can run.
+pub fn highlight(line: &str, settings: &Settings, is_command: impl Fn(&str) -> bool) -> String {
+    let colors = &settings.highlight;
+    let mut result = String::new();
+    let mut expect_command = true;
+
+    for token in tokenize(line) {
+        let text = token.text.to_string();
+
+        let colored = match token.kind {
+            TokenKind::Whitespace => text,
+            TokenKind::Word if expect_command => {
+                if is_command(token.text) {
+                    settings.paint(&text, &colors.command)
+                }
+                else {
+                    settings.paint(&text, &colors.unknown_command)
+                }
+            },
+            TokenKind::Operator => {
+                expect_command = token.separates_commands();
+                settings.paint(&text, &colors.operator)
+            },
+            TokenKind::Word => settings.paint(&text, &colors.argument),
+            TokenKind::Flag => settings.paint(&text, &colors.flag),
+            TokenKind::String => settings.paint(&text, &colors.string),
+            TokenKind::Variable => settings.paint(&text, &colors.variable),
+        };
+
+        if token.kind != TokenKind::Whitespace && token.kind != TokenKind::Operator {
+            expect_command = false;
+        }
+
+        result.push_str(&colored);
+    }
+
+    result
+}
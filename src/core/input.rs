@@ -1,32 +1,188 @@
+use std::{io::{ErrorKind, IsTerminal, Write}, time::Duration};
 
-use std::cell::{RefCell, Ref};
+use chrono::{DateTime, Local};
 
-use text_io::read;
+use crate::commands::Context;
+use crate::core::settings::{Color, EditMode};
+use crate::logger::*;
+use super::{completion::TerminalHelper, editor::LineEditor};
 
-use crate::core::settings::Color;
+/// a line the user entered, with where, when and how it ran.
+#[derive(Clone)]
+pub struct HistoryEntry {
+    pub line: String,
+    pub cwd: String,
+    pub timestamp: DateTime<Local>,
+    /// `None` while the line is still running.
+    pub status: Option<i32>,
+    pub duration: Option<Duration>,
+}
+
+/// what came back from asking the user for a line.
+pub enum Input {
+    Line(String),
+    /// the user pressed Ctrl-C, there's nothing to run.
+    Cancelled,
+    /// stdin was closed, or Ctrl-D was pressed on an empty line.
+    Eof,
+}
 
-pub struct UserInput {
-    hist: RefCell<Vec<String>>,
+/// every line entered this session, oldest first.
+#[derive(Default)]
+pub struct History {
+    entries: Vec<HistoryEntry>,
 }
 
-impl UserInput {
-    pub fn new() -> UserInput {
+impl History {
+    pub fn new() -> History {
         // TODO: in the future read previous user input
         // from a save file.
-        UserInput {
-            hist: RefCell::new(Vec::new())
+        History::default()
+    }
+
+    /// add a line to the history, blank lines aren't worth remembering.
+    pub fn record(&mut self, line: &str, cwd: &str) {
+        if line.trim().is_empty() {
+            return;
         }
+
+        self.entries.push(HistoryEntry {
+            line: line.to_string(),
+            cwd: cwd.to_string(),
+            timestamp: Local::now(),
+            status: None,
+            duration: None,
+        });
+    }
+
+    /// fill in how the most recently recorded line went, once it's done.
+    pub fn finish_last(&mut self, status: i32, duration: Duration) {
+        if let Some(entry) = self.entries.last_mut() {
+            if entry.status.is_none() {
+                entry.status = Some(status);
+                entry.duration = Some(duration);
+            }
+        }
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
     }
 
-    pub fn get(&self, prompt: &str) -> String {
-        print!("{}{}", prompt, Color::light_blue().to_ansi_color());
-        let input: String = read!("{}\n");
-        print!("{}", Color::reset());
-        self.hist.borrow_mut().push(input.clone());
-        input
+    pub fn entries_mut(&mut self) -> &mut Vec<HistoryEntry> {
+        &mut self.entries
     }
 
-    pub fn history(&self) -> Ref<'_, Vec<String>> {
-        self.hist.borrow()
+    /// the most recent history entry that starts with `line`, preferring
+    /// ones that were entered in `cwd`.
+    pub fn suggest(&self, line: &str, cwd: &str) -> Option<String> {
+        if line.is_empty() {
+            return None;
+        }
+
+        let mut candidates = self.entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.line.len() > line.len() && entry.line.starts_with(line));
+
+        let first = candidates.next()?;
+        let best = if first.cwd == cwd {
+            first
+        }
+        else {
+            candidates.find(|entry| entry.cwd == cwd).unwrap_or(first)
+        };
+
+        Some(best.line.clone())
+    }
+}
+
+/// ask the user for a line, with the line editor when stdin is a
+/// terminal.
+pub fn read_line(ctx: &Context<'_>, prompt: &str) -> Input {
+    if std::io::stdin().is_terminal() {
+        let vi_mode = ctx.session.settings.edit_mode == EditMode::Vi;
+        let helper = TerminalHelper::new(ctx);
+        let editor = LineEditor::new(prompt, ctx.session.history.entries(), &helper).vi_mode(vi_mode);
+        match editor.read_line() {
+            Ok(Some(line)) => Input::Line(line),
+            Ok(None) => Input::Eof,
+            Err(e) if e.kind() == ErrorKind::Interrupted => Input::Cancelled,
+            Err(e) => {
+                log!("line editor failed, reading plain input: {}", e);
+                read_plain(prompt, ctx.session.settings.colors)
+            }
+        }
+    }
+    else {
+        read_plain(prompt, ctx.session.settings.colors)
     }
-}
\ No newline at end of file
+}
+
+fn read_plain(prompt: &str, colors: bool) -> Input {
+    // what's typed shows up in color.
+    let (color, reset) = match colors {
+        true => (Color::light_blue().to_ansi_color(), Color::reset()),
+        false => (String::new(), String::new()),
+    };
+    print!("{}{}", prompt, color);
+    let _ = std::io::stdout().flush();
+
+    let mut input = String::new();
+    let result = std::io::stdin().read_line(&mut input);
+    print!("{}", reset);
+
+    match result {
+        Ok(0) => Input::Eof,
+        Ok(_) => Input::Line(input.trim_end_matches(['\n', '\r']).to_string()),
+        Err(e) if e.kind() == ErrorKind::Interrupted => Input::Cancelled,
+        Err(e) => {
+            log!("failed to read input: {}", e);
+            Input::Eof
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(lines: &[(&str, &str)]) -> History {
+        let mut history = History::new();
+        for (line, cwd) in lines {
+            history.record(line, cwd);
+        }
+        history
+    }
+
+    #[test]
+    fn the_most_recent_match_is_suggested() {
+        let history = history(&[("git commit", "/a"), ("git status", "/a"), ("ls", "/a")]);
+        assert_eq!(history.suggest("git", "/a").as_deref(), Some("git status"));
+        assert_eq!(history.suggest("git c", "/a").as_deref(), Some("git commit"));
+    }
+
+    #[test]
+    fn matches_from_the_same_directory_come_first() {
+        let history = history(&[("make test", "/a"), ("make build", "/b")]);
+        assert_eq!(history.suggest("make", "/a").as_deref(), Some("make test"));
+        assert_eq!(history.suggest("make", "/b").as_deref(), Some("make build"));
+        // nothing was run here, so the most recent anywhere will do.
+        assert_eq!(history.suggest("make", "/c").as_deref(), Some("make build"));
+    }
+
+    #[test]
+    fn nothing_to_suggest() {
+        let history = history(&[("ls -l", "/a")]);
+        assert_eq!(history.suggest("", "/a"), None);
+        assert_eq!(history.suggest("ls -l", "/a"), None, "already typed in full");
+        assert_eq!(history.suggest("cat", "/a"), None);
+        assert_eq!(History::new().suggest("ls", "/a"), None);
+    }
+
+    #[test]
+    fn blank_lines_are_not_recorded() {
+        let history = history(&[("  ", "/a"), ("", "/a"), ("pwd", "/a")]);
+        assert_eq!(history.entries().len(), 1);
+    }
+}
@@ -0,0 +1,290 @@
+// Splits an input line into tokens.
+//
+// The lexer never fails, an unterminated string just runs to the end of
+// the line. Every byte of the input ends up in exactly one token, so the
+// tokens can be glued back together to get the original line.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TokenKind {
+    Whitespace,
+    Word,
+    /// a word that starts with a `-`
+    Flag,
+    /// a single or double quoted string, including the quotes.
+    String,
+    /// `$NAME`, `${NAME}` or `$?`
+    Variable,
+    /// `;`, `|`, `||`, `&`, `&&`, `<`, `>` or `>>`
+    Operator,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    /// byte offset into the line.
+    pub start: usize,
+}
+
+impl Token<'_> {
+    /// does this operator end a command, meaning the next word is
+    /// a command name again?
+    pub fn separates_commands(&self) -> bool {
+        self.kind == TokenKind::Operator && matches!(self.text, ";" | "|" | "||" | "&" | "&&")
+    }
+}
+
+const OPERATOR_CHARS: &[char] = &[';', '|', '&', '<', '>'];
+
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && !OPERATOR_CHARS.contains(&c) && c != '"' && c != '\'' && c != '$'
+}
+
+pub fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let bytes = line.as_bytes();
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+
+        let kind = if c.is_whitespace() {
+            while let Some((i, c)) = chars.next_if(|(_, c)| c.is_whitespace()) {
+                end = i + c.len_utf8();
+            }
+            TokenKind::Whitespace
+        }
+        else if c == '"' || c == '\'' {
+            let mut escaped = false;
+            for (i, next) in chars.by_ref() {
+                end = i + next.len_utf8();
+                if escaped {
+                    escaped = false;
+                }
+                else if next == '\\' && c == '"' {
+                    escaped = true;
+                }
+                else if next == c {
+                    break;
+                }
+            }
+            TokenKind::String
+        }
+        else if c == '$' {
+            if let Some((_, '{')) = chars.peek() {
+                for (i, next) in chars.by_ref() {
+                    end = i + next.len_utf8();
+                    if next == '}' {
+                        break;
+                    }
+                }
+            }
+            else if let Some((i, '?')) = chars.peek().copied() {
+                chars.next();
+                end = i + 1;
+            }
+            else {
+                while let Some((i, c)) = chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_') {
+                    end = i + c.len_utf8();
+                }
+            }
+            TokenKind::Variable
+        }
+        else if OPERATOR_CHARS.contains(&c) {
+            // `||`, `&&` and `>>` are the only two character operators.
+            if (c == '|' || c == '&' || c == '>') && bytes.get(end) == Some(&(c as u8)) {
+                chars.next();
+                end += 1;
+            }
+            TokenKind::Operator
+        }
+        else {
+            while let Some((i, c)) = chars.next_if(|(_, c)| is_word_char(*c)) {
+                end = i + c.len_utf8();
+            }
+            if c == '-' {
+                TokenKind::Flag
+            }
+            else {
+                TokenKind::Word
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            text: &line[start..end],
+            start,
+        });
+    }
+
+    tokens
+}
+
+/// how a command is joined to the one before it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Joiner {
+    /// the first command, or after `;`. it always runs.
+    Always,
+    /// after `&&`, it only runs if the last command worked.
+    IfSuccess,
+    /// after `||`, it only runs if the last command failed.
+    IfFailure,
+}
+
+/// split a line into the commands separated by `;`, `&&` and `||`,
+/// quoted ones don't count.
+pub fn split_commands(line: &str) -> Vec<(Joiner, &str)> {
+    let mut commands = Vec::new();
+    let mut start = 0;
+    let mut joiner = Joiner::Always;
+
+    for token in tokenize(line) {
+        let next = match (token.kind, token.text) {
+            (TokenKind::Operator, ";") => Joiner::Always,
+            (TokenKind::Operator, "&&") => Joiner::IfSuccess,
+            (TokenKind::Operator, "||") => Joiner::IfFailure,
+            _ => continue,
+        };
+        commands.push((joiner, &line[start..token.start]));
+        start = token.start + token.text.len();
+        joiner = next;
+    }
+    commands.push((joiner, &line[start..]));
+
+    commands
+}
+
+/// split a command into its words, the way they're handed to it. quotes
+/// are taken off (`"hi there"` is one word, `hi there`) and `""` is an
+/// empty word, other tokens are kept as they were typed.
+pub fn words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current: Option<String> = None;
+
+    for token in tokenize(line) {
+        if token.kind == TokenKind::Whitespace {
+            words.extend(current.take());
+            continue;
+        }
+
+        let word = current.get_or_insert_with(String::new);
+        if token.kind == TokenKind::String {
+            unquote(token.text, word);
+        }
+        else {
+            word.push_str(token.text);
+        }
+    }
+    words.extend(current);
+
+    words
+}
+
+/// the inside of a quoted string, `\"` and `\\` are unescaped in double
+/// quotes. an unterminated string runs to the end.
+fn unquote(text: &str, out: &mut String) {
+    let mut chars = text.chars();
+    let Some(quote) = chars.next() else { return };
+
+    while let Some(c) = chars.next() {
+        if c == quote {
+            break;
+        }
+        if c == '\\' && quote == '"' {
+            match chars.next() {
+                Some(next) if next == '"' || next == '\\' => out.push(next),
+                Some(next) => {
+                    out.push(c);
+                    out.push(next);
+                },
+                None => out.push(c),
+            }
+            continue;
+        }
+        out.push(c);
+    }
+}
+
+/// the other way around from `words`, `word` written so it comes back
+/// out of `words` as one word. anything that would be split up or read
+/// as something else goes in double quotes, a trailing `/` is left out
+/// of them so a path can be typed on.
+pub fn quote(word: &str) -> String {
+    if !word.is_empty() && word.chars().all(is_word_char) {
+        return word.to_string();
+    }
+
+    let (inside, slash) = match word.strip_suffix('/') {
+        Some(inside) if !inside.is_empty() => (inside, "/"),
+        _ => (word, ""),
+    };
+    let mut quoted = String::from("\"");
+    for c in inside.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted.push_str(slash);
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_spaces_are_one_separator() {
+        assert_eq!(words("mkdir  x"), vec!["mkdir", "x"]);
+        assert_eq!(words("  ls  dir  "), vec!["ls", "dir"]);
+        assert_eq!(words("help \t cd"), vec!["help", "cd"]);
+    }
+
+    #[test]
+    fn quotes_are_taken_off() {
+        assert_eq!(words(r#"echo "hi there""#), vec!["echo", "hi there"]);
+        assert_eq!(words("echo 'a  b' c"), vec!["echo", "a  b", "c"]);
+        assert_eq!(words(r#"echo pre"fix"ed"#), vec!["echo", "prefixed"]);
+        assert_eq!(words(r#"echo "" x"#), vec!["echo", "", "x"]);
+    }
+
+    #[test]
+    fn escapes_in_double_quotes() {
+        assert_eq!(words(r#"echo "say \"hi\"" "a\\b" "\n""#), vec!["echo", r#"say "hi""#, r"a\b", r"\n"]);
+        assert_eq!(words(r"echo 'no \' escapes"), vec!["echo", r"no \", "escapes"]);
+    }
+
+    #[test]
+    fn unterminated_strings_run_to_the_end() {
+        assert_eq!(words(r#"echo "open  end"#), vec!["echo", "open  end"]);
+    }
+
+    #[test]
+    fn split_commands_ignores_quoted_operators() {
+        assert_eq!(split_commands("a; b && c || d"), vec![
+            (Joiner::Always, "a"),
+            (Joiner::Always, " b "),
+            (Joiner::IfSuccess, " c "),
+            (Joiner::IfFailure, " d"),
+        ]);
+        assert_eq!(split_commands("echo 'a; b'"), vec![(Joiner::Always, "echo 'a; b'")]);
+    }
+
+    #[test]
+    fn quote_round_trips() {
+        for word in ["plain", "My Dir", "", "it's", r#"say "hi""#, r"back\slash", "a;b", "$HOME", "x&&y", "tab\there"] {
+            assert_eq!(words(&quote(word)), vec![word], "{:?} quoted as {:?}", word, quote(word));
+        }
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("some/dir/"), "some/dir/");
+        assert_eq!(quote("My Dir"), r#""My Dir""#);
+        assert_eq!(quote("My Dir/"), r#""My Dir"/"#);
+        assert_eq!(quote(r#"a"b"#), r#""a\"b""#);
+    }
+
+    #[test]
+    fn a_quoted_directory_can_be_typed_on() {
+        assert_eq!(words(&format!("cd {}sub", quote("My Dir/"))), vec!["cd", "My Dir/sub"]);
+    }
+}
@@ -1,164 +1,315 @@
 pub mod input;
 pub mod settings;
+pub mod editor;
+pub mod completion;
+pub mod lexer;
+pub mod highlight;
+pub mod expansion;
+pub mod vi;
+pub mod signals;
+pub mod suggest;
+pub mod builder;
+pub mod session;
 
-use std::cell::{Cell, RefCell, Ref};
-use crate::commands::{Commands, impls::{
-    HelpCommand, 
-    HistoryCommand, 
-    CdCommand, 
-    LsCommand, 
-    ManCommand, 
-    ExitCommand, 
-    RmDirCommand, 
-    MkDirCommand, 
-    ConfigCommand, ClearCommand, WhereCommand, PwdCommand
-}, AsStr};
-use self::{input::UserInput, settings::Settings};
+use std::{io::{BufRead, IsTerminal, Write}, time::{Duration, Instant}};
+use crate::commands::{Commands, Context, io::{IoContext, Streams}, plugin::Plugins};
+use crate::error::UniError;
+use self::{builder::TerminalBuilder, input::Input, lexer::Joiner, session::Session, settings::Settings};
 
 pub struct Terminal {
     cmds: Commands,
-    inp: UserInput,
-    _settings: Settings,
-
-    // flags (how to bits work??)
-    should_quit: Cell<bool>,
+    plugins: Plugins,
+    session: Session,
+    /// what commands read from and write to.
+    streams: Streams,
+    /// how long `new` took, for `--timing`.
+    startup_time: Duration,
 }
 
-pub enum TerminalInitError {
-    CantGetDirectory(String),
+/// what a line printed and how it went, from `Terminal::run_line`.
+#[derive(Debug, Clone, Default)]
+pub struct LineOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: i32,
 }
 
-impl Into<String> for TerminalInitError {
-    fn into(self) -> String {
-        match self {
-            TerminalInitError::CantGetDirectory(msg) => {
-                msg
-            }
-        }
+// commands are handed a `Context`, it has to stay `Send` so they can be
+// run on other threads, and so does what they fail with.
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<Context<'static>>();
+    assert_send::<UniError>();
+};
+
+impl Terminal {
+    pub fn new() -> Result<Terminal, UniError> {
+        TerminalBuilder::new().interactive(true).build()
     }
-}
 
-#[cfg(windows)]
-const USER_NAME_ENV_NAME: &'static str = "USERNAME";
-#[cfg(not(windows))]
-const USER_NAME_ENV_NAME: &'static str = "USER";
+    /// a terminal for running commands without a user, no prompts, no
+    /// colors, and it starts in the directory it was run from.
+    pub fn non_interactive() -> Result<Terminal, UniError> {
+        TerminalBuilder::new().build()
+    }
 
-#[cfg(windows)]
-const PATH_ENVVAR_SEP: char = ';';
-#[cfg(not(windows))]
-const PATH_ENVVAR_SEP: char = ':';
+    pub fn execute(&mut self) -> ! {
+        if let Err(e) = signals::install() {
+            self.report_error(&UniError::Custom(e));
+        }
+        // pick up where the last session was left.
+        self.session.settings.sync_path();
 
-const PATH_ENVIRONMENT_VAR: &'static str = "PATH";
+        // Ctrl-D's pressed in a row, for `ignoreeof`.
+        let mut eof_count = 0;
 
-impl Terminal {
-    pub fn new() -> Result<Terminal, TerminalInitError> {
-        let mut commands = Commands::new();
-
-        commands.push(Box::new(HelpCommand));
-        commands.push(Box::new(HistoryCommand));
-        commands.push(Box::new(CdCommand));
-        commands.push(Box::new(LsCommand));
-        commands.push(Box::new(ManCommand));
-        commands.push(Box::new(ExitCommand));
-        commands.push(Box::new(RmDirCommand));
-        commands.push(Box::new(MkDirCommand));
-        commands.push(Box::new(ConfigCommand));
-        commands.push(Box::new(ClearCommand));
-        commands.push(Box::new(WhereCommand));
-        commands.push(Box::new(PwdCommand));
-
-        let path = match std::env::var(PATH_ENVIRONMENT_VAR) {
-            Ok(path) => Some(path),
-            Err(e) => {
-                eprintln!("failed to get `PATH` environment variable. ({})", e.to_string());
-                None
-            }
-        };
+        while !self.session.should_quit() {
+            let last = self.session.outcome();
+            let built_prompt = self.session.settings.build_prompt(&last);
+            let input = input::read_line(&self.context(), built_prompt.as_str());
+            let mut data = match input {
+                Input::Line(line) => line,
+                Input::Cancelled => {
+                    eof_count = 0;
+                    self.session.set_status(130);
+                    continue;
+                },
+                Input::Eof => {
+                    eof_count += 1;
+                    let needed = self.session.settings.ignore_eof.max(1);
+                    // a closed stdin stays closed, so only a terminal gets to ignore it.
+                    if eof_count < needed && std::io::stdin().is_terminal() {
+                        eprintln!("use \"exit\" to leave ({} more Ctrl-D to exit)", needed - eof_count);
+                    }
+                    else {
+                        let status = self.session.status();
+                        self.session.quit_with(status);
+                    }
+                    continue;
+                }
+            };
+            eof_count = 0;
 
-        if let Some(path) = path {
-            let all_directorys: Vec<&str> = path.split(PATH_ENVVAR_SEP).collect();
-            for dir in all_directorys {
-                match commands.add_path_folder(dir.to_string()) {
-                    Ok(()) => {},
-                    // TODO: log this information somewhere.
-                    Err(_) => ()
+            if self.session.settings.history_expansion {
+                let expanded = expansion::expand_history(&data, self.session.history.entries());
+                match expanded {
+                    Ok(Some(line)) => {
+                        // show what is actually about to run.
+                        println!("{}", line);
+                        data = line;
+                    },
+                    Ok(None) => (),
+                    Err(e) => {
+                        self.report_error(&e);
+                        continue;
+                    }
                 }
             }
+
+            self.run(&data);
         }
 
-        let current_path = match std::env::current_dir() {
-            Ok(path) => {
-                let p = path.into_os_string();
-                p.into_string().unwrap()
-            },
-            Err(e) => {
-                return Err(
-                    TerminalInitError::CantGetDirectory(e.to_string())
-                );
+        self.finish()
+    }
+
+    /// run every line from `reader` without prompting, then exit. this is
+    /// used for `-c` and when stdin isn't a terminal.
+    pub fn execute_script(&mut self, reader: impl BufRead) -> ! {
+        if let Err(e) = signals::install() {
+            self.report_error(&UniError::Custom(e));
+        }
+
+        for line in reader.lines() {
+            if self.session.should_quit() {
+                break;
             }
-        };
 
-        // TODO: check if user even wants their name shown.
-        // IF we cant find the user name, just dont use one.
-        let user_name = match std::env::var(USER_NAME_ENV_NAME) {
-            Ok(name) => Some(name),
-            Err(_) => {
-                None
+            match line {
+                Ok(line) => self.run(&line),
+                Err(e) => {
+                    self.report_error(&e.into());
+                    self.session.set_status(1);
+                    break;
+                }
             }
-        };
+        }
+
+        self.finish()
+    }
+
+    /// run a line and capture what it printed. it reads from the input
+    /// stream the terminal was given.
+    pub fn run_line(&mut self, line: &str) -> LineOutput {
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+
+        {
+            let mut io = self.streams.capture(
+                &mut stdout,
+                &mut stderr,
+                self.session.env.clone(),
+                self.session.cwd().to_string(),
+            );
+            let mut ctx = Context {
+                session: &mut self.session,
+                commands: &self.cmds,
+                plugins: &self.plugins,
+            };
+            run_with(&mut ctx, line, &mut io, false);
+        }
 
-        let prompt = Settings::from_save_or_default(current_path);
-        *prompt.get_user_name() = user_name;
+        LineOutput {
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            status: self.session.status(),
+        }
+    }
 
-        Ok(Self {
-            cmds: commands,
-            inp: UserInput::new(),
-            _settings: prompt,
-            should_quit: Cell::new(false),
-        })
+    /// run a line with the terminal's own streams. we own the process
+    /// here, so it follows the session into whatever directory it ends
+    /// up in.
+    fn run(&mut self, line: &str) {
+        let mut io = self.streams.io(self.session.env.clone(), self.session.cwd().to_string());
+        let mut ctx = Context {
+            session: &mut self.session,
+            commands: &self.cmds,
+            plugins: &self.plugins,
+        };
+        run_with(&mut ctx, line, &mut io, true);
     }
 
-    pub fn execute(&self) -> ! {
-        let this_ref = RefCell::new(self);
+    /// show an error the way every error in the REPL is shown.
+    pub fn report_error(&self, e: &UniError) {
+        eprintln!("{}", e.render(self.session.settings.error_colors()));
+    }
 
-        while !self.should_quit.get() {
-            let built_prompt = self.settings().build_prompt();
-            let data = self.input().get(built_prompt.as_str());
-            match self.commands().try_execute(this_ref.borrow(), data) {
-                Ok(_) => {},
+    /// save (when interactive) and exit the process.
+    fn finish(&self) -> ! {
+        if self.session.is_interactive() {
+            match self.settings().save() {
+                Ok(_) => (),
                 Err(e) => {
-                    eprintln!("ERROR: {}", e.as_str());
+                    self.report_error(&e);
                 }
             }
         }
 
-        match self.settings().save() {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("ERROR: {}", e.as_str());
-            }
+        std::process::exit(self.exit_status().unwrap_or(self.status()));
+    }
+
+    /// what commands get to see and change.
+    pub fn context(&mut self) -> Context<'_> {
+        Context {
+            session: &mut self.session,
+            commands: &self.cmds,
+            plugins: &self.plugins,
         }
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn session_mut(&mut self) -> &mut Session {
+        &mut self.session
+    }
 
-        std::process::exit(0);
+    /// what the terminal exits with, once something asked it to quit.
+    pub fn exit_status(&self) -> Option<i32> {
+        self.session.exit_status()
     }
 
-    pub fn input(&self) -> &UserInput {
-        &self.inp
+    /// the exit status of the last command.
+    pub fn status(&self) -> i32 {
+        self.session.status()
     }
 
-    pub fn quit(&self) {
-        self.should_quit.set(true);
+    /// how long it took to get ready.
+    pub fn startup_time(&self) -> Duration {
+        self.startup_time
     }
 
     pub fn settings(&self) -> &Settings {
-        &self._settings
+        &self.session.settings
     }
 
-    pub fn current_path(&self) -> Ref<'_, String> {
-        self.settings().get_path_view()
+    pub fn current_path(&self) -> &str {
+        self.session.cwd()
     }
 
     pub fn commands(&self) -> &Commands {
         &self.cmds
     }
-}
\ No newline at end of file
+
+    pub fn plugins(&self) -> &Plugins {
+        &self.plugins
+    }
+}
+
+/// run a line, commands separated by `;` run one after the other.
+/// `a && b` only runs `b` when `a` works, `a || b` only when it
+/// fails. lines starting with `#` are comments. `owns_process` moves
+/// the process along with the session after every command.
+fn run_with(ctx: &mut Context<'_>, line: &str, io: &mut IoContext<'_>, owns_process: bool) {
+    if line.trim().is_empty() || line.trim_start().starts_with('#') {
+        return;
+    }
+
+    // recorded before running so `history` can see itself.
+    let session = &mut *ctx.session;
+    session.history.record(line, &session.settings.path);
+    let started = Instant::now();
+    signals::reset();
+
+    for (joiner, command) in lexer::split_commands(line) {
+        let command = command.trim();
+        if command.is_empty() {
+            continue;
+        }
+
+        let skip = match joiner {
+            Joiner::Always => false,
+            Joiner::IfSuccess => ctx.session.status() != 0,
+            Joiner::IfFailure => ctx.session.status() == 0,
+        };
+        if skip {
+            continue;
+        }
+
+        let previous_status = ctx.session.status();
+
+        // an earlier command might have moved us, or changed the environment.
+        io.cwd = ctx.cwd().to_string();
+        io.env = ctx.session.env.clone();
+        let commands = ctx.commands;
+        match commands.try_execute(ctx, io, command.to_string()) {
+            Ok(outcome) => ctx.session.set_outcome(outcome),
+            Err(e) => {
+                let _ = writeln!(io.stderr, "{}", e.render(ctx.session.settings.error_colors()));
+                ctx.session.set_status(1);
+            }
+        }
+        let _ = io.flush();
+        if owns_process {
+            ctx.session.settings.sync_path();
+        }
+
+        if ctx.session.should_quit() {
+            ctx.session.default_exit_status(previous_status);
+            break;
+        }
+
+        // Ctrl-C skips the rest of the line too, whether it reached us or
+        // only the command in the foreground, which it killed.
+        #[cfg(unix)]
+        let killed = ctx.session.outcome().signal == Some(libc::SIGINT);
+        #[cfg(not(unix))]
+        let killed = false;
+        if signals::interrupted() || killed {
+            break;
+        }
+    }
+
+    let status = ctx.session.status();
+    ctx.session.history.finish_last(status, started.elapsed());
+}
@@ -0,0 +1,102 @@
+// The state of a running shell.
+//
+// Everything a command is allowed to change lives here, as plain data:
+// the settings (which hold the working directory), the environment, the
+// history and how the last command went. Commands get it mutably through
+// their `Context`, so there's nothing to borrow at runtime.
+
+use crate::commands::{io::Env, Outcome};
+use super::{input::History, settings::Settings};
+
+pub struct Session {
+    pub settings: Settings,
+    /// the environment commands run with.
+    pub env: Env,
+    pub history: History,
+
+    /// false when running `-c` or commands piped into stdin, there's
+    /// no prompt and nothing is saved.
+    interactive: bool,
+    /// how the last command that ran went.
+    last_outcome: Outcome,
+    should_quit: bool,
+    /// what the process exits with once we quit.
+    exit_status: Option<i32>,
+}
+
+impl Session {
+    pub fn new(settings: Settings, env: Env, interactive: bool) -> Session {
+        Session {
+            settings,
+            env,
+            history: History::new(),
+            interactive,
+            last_outcome: Outcome::success(),
+            should_quit: false,
+            exit_status: None,
+        }
+    }
+
+    /// the working directory.
+    pub fn cwd(&self) -> &str {
+        &self.settings.path
+    }
+
+    /// move to `dir`, which should already be an absolute path. this
+    /// doesn't touch the process's own working directory.
+    pub fn set_cwd(&mut self, dir: String) {
+        self.settings.path = dir;
+    }
+
+    /// stop once the current command is done, exiting with the status
+    /// of the command before it.
+    pub fn quit(&mut self) {
+        self.should_quit = true;
+    }
+
+    /// stop once the current command is done, exiting with `status`.
+    pub fn quit_with(&mut self, status: i32) {
+        self.exit_status = Some(status);
+        self.should_quit = true;
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.should_quit
+    }
+
+    /// what the terminal exits with, once something asked it to quit.
+    pub fn exit_status(&self) -> Option<i32> {
+        self.should_quit.then(|| self.exit_status.unwrap_or(self.status()))
+    }
+
+    /// a plain `exit` leaves with `status`, the status of whatever ran
+    /// before it.
+    pub(crate) fn default_exit_status(&mut self, status: i32) {
+        if self.exit_status.is_none() {
+            self.exit_status = Some(status);
+        }
+    }
+
+    /// the exit status of the last command.
+    pub fn status(&self) -> i32 {
+        self.last_outcome.code
+    }
+
+    pub fn set_status(&mut self, status: i32) {
+        self.set_outcome(Outcome::exited(status));
+    }
+
+    /// how the last command went.
+    pub fn outcome(&self) -> Outcome {
+        self.last_outcome
+    }
+
+    pub fn set_outcome(&mut self, outcome: Outcome) {
+        self.last_outcome = outcome;
+    }
+
+    /// false for `-c` and scripts, there's nobody to ask questions.
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+}
@@ -1,9 +1,9 @@
-use std::{cell::{RefCell, RefMut, Ref}, io, fs::DirEntry, path::Path};
+use std::{io, fs::DirEntry, path::Path};
 use serde::{Serialize, Deserialize};
 
-use crate::{commands::AsStr, logger::*};
+use crate::{commands::Outcome, error::{SaveError, UniError}, logger::*};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Copy)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -11,18 +11,10 @@ pub struct Color {
 }
 
 pub trait WithConsoleColor {
-    fn with_color(&self, color: Ref<'_, Color>) -> String;
     fn rgb(&self, color: &Color) -> String;
 }
 
 impl WithConsoleColor for String {
-    fn with_color(&self, color: Ref<'_, Color>) -> String {
-        format!("{}{}{}", 
-            color.to_ansi_color(),
-            self,
-            Color::reset())
-    }
-
     fn rgb(&self, color: &Color) -> String {
         format!("{}{}{}",
             color.to_ansi_color(),
@@ -73,79 +65,188 @@ impl Color {
 
 #[derive(Serialize, Deserialize)]
 pub struct Settings {
-    path: RefCell<String>,
-    user_name: RefCell<Option<String>>,
-    git_branch: RefCell<Option<String>>,
+    /// the working directory, saved so the next session starts there.
+    pub path: String,
+    pub user_name: Option<String>,
+    pub git_branch: Option<String>,
+
+    pub path_color: Color,
+    pub user_name_color: Color,
+    pub git_branch_color: Color,
+
+    #[serde(default)]
+    pub highlight: HighlightColors,
+
+    #[serde(default)]
+    pub errors: ErrorColors,
+
+    /// expand `!!`, `!n`, `^old^new` etc. before running a line.
+    #[serde(default = "enabled")]
+    pub history_expansion: bool,
+
+    #[serde(default)]
+    pub edit_mode: EditMode,
+
+    /// how many Ctrl-D's in a row it takes to exit, 0 and 1 both mean one.
+    #[serde(default)]
+    pub ignore_eof: u32,
 
-    path_color: RefCell<Color>,
-    user_name_color: RefCell<Color>,
-    git_branch_color: RefCell<Color>,
+    /// extensions that can be left off when running something from PATH.
+    #[serde(default = "default_path_extensions")]
+    pub path_extensions: Vec<String>,
+
+    /// when to print the "exited with status" line after a command.
+    #[serde(default)]
+    pub report_status: ReportStatus,
+
+    /// offer to run the closest command when one doesn't exist.
+    #[serde(default)]
+    pub autocorrect: bool,
+
+    /// run with the name and arguments of a command that doesn't exist.
+    #[serde(default)]
+    pub not_found_handler: Option<String>,
+
+    /// where these were loaded from and get saved to.
+    #[serde(skip)]
+    config_folder: Option<String>,
+
+    /// color escapes are only written when this is on, it's off when
+    /// nobody is there to see them.
+    #[serde(skip, default = "enabled")]
+    pub colors: bool,
+}
+
+/// when to say how a command that isn't a builtin finished.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    Never,
+    /// only when it didn't exit with 0.
+    #[default]
+    Failures,
+    Always,
+}
+
+impl ReportStatus {
+    pub fn should_report(&self, outcome: &Outcome) -> bool {
+        match self {
+            ReportStatus::Never => false,
+            ReportStatus::Failures => !outcome.is_success(),
+            ReportStatus::Always => true,
+        }
+    }
+}
+
+/// which key bindings the line editor uses.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EditMode {
+    #[default]
+    Emacs,
+    Vi,
 }
 
 #[cfg(windows)]
-pub const CONFIG_PATH_DIR_ENVVAR: &'static str = "APPDATA";
+fn default_path_extensions() -> Vec<String> {
+    [".exe", ".bat", ".cmd", ".com"].iter().map(|ext| ext.to_string()).collect()
+}
 
 #[cfg(not(windows))]
-pub const CONFIG_PATH_DIR_ENVVAR: &'static str = "HOME";
+fn default_path_extensions() -> Vec<String> {
+    Vec::new()
+}
 
-pub const CONFIG_DIR_NAME: &'static str = ".term-rs";
-pub const SETTINGS_FILE_NAME: &'static str = "settings.json";
+fn enabled() -> bool {
+    true
+}
 
-pub const DEFAULT_PATH_COLOR: Color = Color::new(20, 255, 247);
-pub const DEFAULT_USERNAME_COLOR: Color = Color::new(179, 30, 0);
-pub const DEFAULT_GIT_BRANCH_COLOR: Color = Color::new(255, 204, 246);
+/// the colors used to highlight the line while you're typing it.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct HighlightColors {
+    /// the first word, when it's a command that exists.
+    pub command: Color,
+    /// the first word, when no command has that name.
+    pub unknown_command: Color,
+    pub argument: Color,
+    pub string: Color,
+    pub flag: Color,
+    pub variable: Color,
+    pub operator: Color,
+    /// the suggestion from your history shown after the cursor.
+    pub suggestion: Color,
+}
 
-pub enum SaveError {
-    NoSuitablePath(String),
-    FailedToSerialize(String),
-    IoError(String),
+impl Default for HighlightColors {
+    fn default() -> Self {
+        Self {
+            command: Color::light_green(),
+            unknown_command: Color::light_red(),
+            argument: Color::light_blue(),
+            string: Color::new(230, 219, 116),
+            flag: Color::new(102, 217, 239),
+            variable: Color::new(253, 151, 31),
+            operator: Color::new(249, 38, 114),
+            suggestion: Color::new(128, 128, 128),
+        }
+    }
 }
 
-impl AsStr for SaveError {
-    fn as_str(&self) -> String {
-        match self {
-            Self::NoSuitablePath(m) => {
-                format!("no suitable path: {m}")
-            },
-            Self::FailedToSerialize(m) => {
-                format!("failed to serialize: {m}")
-            },
-            Self::IoError(m) => {
-                format!("IoError: {m}")
-            }
+/// the colors errors are shown in.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct ErrorColors {
+    /// the `error[code]` in front of the message.
+    pub error: Color,
+    pub hint: Color,
+}
+
+impl Default for ErrorColors {
+    fn default() -> Self {
+        Self {
+            error: Color::light_red(),
+            hint: Color::new(102, 217, 239),
         }
     }
 }
 
-type PromptOptionMut<'a> = RefMut<'a, Option<String>>;
+#[cfg(windows)]
+pub const CONFIG_PATH_DIR_ENVVAR: &str = "APPDATA";
+
+#[cfg(not(windows))]
+pub const CONFIG_PATH_DIR_ENVVAR: &str = "HOME";
+
+pub const CONFIG_DIR_NAME: &str = ".term-rs";
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+
+pub const DEFAULT_PATH_COLOR: Color = Color::new(20, 255, 247);
+pub const DEFAULT_USERNAME_COLOR: Color = Color::new(179, 30, 0);
+pub const DEFAULT_GIT_BRANCH_COLOR: Color = Color::new(255, 204, 246);
 
 impl Settings {
-    pub fn from_save_or_default(init_path: String) -> Settings {
-        if let Some(config) = Self::get_config_folder() {
-            // read the file contents then initialize ourself
-            // with it.
-            let path_to_settings = format!("{}/{}", config, SETTINGS_FILE_NAME);
-            let path = Path::new(&path_to_settings);
-            let contents = match std::fs::read_to_string(&path) {
-                Ok(contents) => contents,
-                Err(_) => {
-                    return Self::new(init_path);
-                }
-            };
-            let deserialized: Self = match serde_json::from_str(&contents) {
-                Ok(o) => o,
-                Err(e) => {
-                    eprintln!("failed to deserialize! ({})", e.to_string());
-                    return Self::new(init_path);
-                }
-            };
-            // set the working directory to whatever it was saved to.
-            deserialized.sync_path();
-            return deserialized;
-        }
-        else {
-            Self::new(init_path)
-        }
+    /// the settings saved in `config_folder`, or the defaults when
+    /// nothing was saved there.
+    pub fn from_save_or_default(config_folder: Option<String>, init_path: String) -> Settings {
+        let mut settings = Self::load(config_folder.as_deref()).unwrap_or_else(|| Self::new(init_path));
+        settings.config_folder = config_folder;
+        settings
+    }
+
+    fn load(config_folder: Option<&str>) -> Option<Settings> {
+        // read the file contents then initialize ourself
+        // with it.
+        let path_to_settings = format!("{}/{}", config_folder?, SETTINGS_FILE_NAME);
+        let path = Path::new(&path_to_settings);
+        let contents = std::fs::read_to_string(path).ok()?;
+        let deserialized: Self = match serde_json::from_str(&contents) {
+            Ok(o) => o,
+            Err(e) => {
+                eprintln!("failed to deserialize! ({})", e);
+                return None;
+            }
+        };
+        Some(deserialized)
     }
 
     pub fn new(init_path: String) -> Settings {
@@ -154,27 +255,33 @@ impl Settings {
         // wants their username/machine name shown.
 
         Self {
-            path: RefCell::new(init_path),
-            user_name: RefCell::new(None),
-            git_branch: RefCell::new(None),
-
-            path_color: RefCell::new(DEFAULT_PATH_COLOR),
-            user_name_color: RefCell::new(DEFAULT_USERNAME_COLOR),
-            git_branch_color: RefCell::new(DEFAULT_GIT_BRANCH_COLOR)
+            path: init_path,
+            user_name: None,
+            git_branch: None,
+
+            path_color: DEFAULT_PATH_COLOR,
+            user_name_color: DEFAULT_USERNAME_COLOR,
+            git_branch_color: DEFAULT_GIT_BRANCH_COLOR,
+
+            highlight: HighlightColors::default(),
+            errors: ErrorColors::default(),
+
+            history_expansion: true,
+            edit_mode: EditMode::Emacs,
+            ignore_eof: 0,
+            path_extensions: default_path_extensions(),
+            report_status: ReportStatus::default(),
+            autocorrect: false,
+            not_found_handler: None,
+            config_folder: None,
+            colors: true,
         }
     } 
 
-    pub fn get_git_repo(&self) -> PromptOptionMut<'_> {
-        self.git_branch.borrow_mut()
-    }
-
-    pub fn get_path(&self) -> RefMut<'_, String> {
-        self.path.borrow_mut()
-    }
-
+    /// move the whole process into `path`. commands don't need this, only
+    /// the shell that owns the process does it.
     pub fn sync_path(&self) {
-        let local_path = self.get_path();
-        let path = Path::new(&*local_path);
+        let path = Path::new(&self.path);
         match std::env::set_current_dir(path) {
             Ok(_) => (),
             Err(e) => {
@@ -183,26 +290,6 @@ impl Settings {
         };
     }
 
-    pub fn get_path_view(&self) -> Ref<'_, String> {
-        self.path.borrow()
-    }
-
-    pub fn get_user_name(&self) -> PromptOptionMut<'_> {
-        self.user_name.borrow_mut()
-    }
-
-    pub fn get_path_color(&self) -> RefMut<'_, Color> {
-        self.path_color.borrow_mut()
-    }
-
-    pub fn get_username_color(&self) -> RefMut<'_, Color> {
-        self.user_name_color.borrow_mut()
-    }
-
-    pub fn get_branch_color(&self) -> RefMut<'_, Color> {
-        self.git_branch_color.borrow_mut()
-    }
-
     fn visit_git_head(&self, file: &Path) -> io::Result<String> {
         // basic parsing of a ".git/HEAD" file.
         // they look like this:
@@ -234,23 +321,21 @@ impl Settings {
         }
     }
 
-    fn visit_dot_git_folder(&self, entry: &DirEntry) -> io::Result<()> {
+    fn visit_dot_git_folder(&mut self, entry: &DirEntry) -> io::Result<()> {
         for file in std::fs::read_dir(entry.path())? {
             let file = file?;
             let path = file.path();
 
-            if path.is_file() {
-                if path.file_name().unwrap().to_str().unwrap() == "HEAD" {
-                    let branch = self.visit_git_head(&path)?; 
-                    *self.get_git_repo() = Some(branch);
-                }
+            if path.is_file() && path.file_name().unwrap().to_str().unwrap() == "HEAD" {
+                let branch = self.visit_git_head(&path)?; 
+                self.git_branch = Some(branch);
             }
         }
 
         Ok(())
     }
 
-    fn handle_git_business(&self) -> io::Result<()> {
+    fn handle_git_business(&mut self) -> io::Result<()> {
         // *path (the path stored in self.path)
         // check if the path or any of its parents
         // contain a ".git" folder.
@@ -259,7 +344,7 @@ impl Settings {
         // do we keep track of the directory with the ".git"
         // folder and just see if were a sub-directory of it?
 
-        let dir = std::fs::read_dir(&*self.get_path_view())?;
+        let dir = std::fs::read_dir(&self.path)?;
         let mut found_git_folder = false;
 
         for entry in dir {
@@ -274,94 +359,92 @@ impl Settings {
         }
 
         if !found_git_folder {
-            *self.get_git_repo() = None;
+            self.git_branch = None;
         }
 
         Ok(())
     }
 
-    pub fn build_prompt(&self) -> String {
+    /// `text` in `color`, or just `text` when colors are off.
+    pub fn paint(&self, text: &str, color: &Color) -> String {
+        if !self.colors {
+            return text.to_string();
+        }
+        text.to_string().rgb(color)
+    }
+
+    /// the colors to show errors in, `None` when colors are off.
+    pub fn error_colors(&self) -> Option<&ErrorColors> {
+        self.colors.then_some(&self.errors)
+    }
+
+    /// `last` is how the last command went, failures show their status.
+    pub fn build_prompt(&mut self, last: &Outcome) -> String {
         let mut result = String::new();
         result.push_str(
-            self.path.borrow().with_color(self.path_color.borrow()).as_str()
+            self.paint(&self.path, &self.path_color).as_str()
         );
 
-        if let Some(user_name) = &*self.user_name.borrow() {
-            result.push_str(format!("@{}", user_name.with_color(self.user_name_color.borrow())).as_str());
+        if let Some(user_name) = &self.user_name {
+            result.push_str(format!("@{}", self.paint(user_name, &self.user_name_color)).as_str());
         }
 
         // dont handle this, it doesnt really matter.
         let _ = self.handle_git_business();
 
-        if let Some(git_repo) = &*self.git_branch.borrow() {
-            result.push_str(format!("({})", git_repo.with_color(self.git_branch_color.borrow())).as_str());
+        if let Some(git_repo) = &self.git_branch {
+            result.push_str(format!("({})", self.paint(git_repo, &self.git_branch_color)).as_str());
+        }
+
+        if !last.is_success() {
+            result.push_str(format!("[{}]", self.paint(&last.code.to_string(), &Color::light_red())).as_str());
         }
 
         result.push_str("> ");
         result
     }
 
-    /// get the folder where our config folder lives.
-    pub fn get_config_location() -> Option<String> {
-        match std::env::var(CONFIG_PATH_DIR_ENVVAR) {
-            Ok(path) => Some(path),
-            Err(_) => {
-                None
-            }
-        }
+    /// where the config folder is unless told otherwise, it might not
+    /// exist yet.
+    pub fn default_config_folder() -> Option<String> {
+        let location = std::env::var(CONFIG_PATH_DIR_ENVVAR).ok()?;
+        Some(format!("{}/{}", location, CONFIG_DIR_NAME))
     }
 
-    // get the actual config folder.
-    pub fn get_config_folder() -> Option<String> {
-        let location = Self::get_config_location()?;
-        let dir = format!("{}/{}", location, CONFIG_DIR_NAME);
-        let path = Path::new(&dir);
-
-        if !path.exists() {
-            None
-        }
-        else {
-            Some(dir)
-        }
+    /// the folder these settings are saved in.
+    pub fn config_folder(&self) -> Option<&str> {
+        self.config_folder.as_deref()
     }
 
-    pub fn save(&self) -> Result<(), SaveError> {
+    pub fn save(&self) -> Result<(), UniError> {
         // serialize this class firstly
         let serialized = match serde_json::to_string(&self) {
             Ok(serialized) => serialized,
             Err(e) => {
-                eprintln!("failed to serialize settings: {}", e.to_string());
                 return Err(
-                    SaveError::FailedToSerialize(
-                        e.to_string()
-                    )
+                    SaveError::FailedToSerialize(e).into()
                 );
             }
         };
 
         // find our save location
-        let home_dir = match std::env::var(CONFIG_PATH_DIR_ENVVAR) {
-            Ok(path) => path,
-            Err(e) => {
+        let full_path = match self.config_folder() {
+            Some(path) => path,
+            None => {
                 return Err(
-                    SaveError::NoSuitablePath(e.to_string())
+                    SaveError::NoSuitablePath.into()
                 )
             }
         };
 
-        // concat the config folder with our config directory name
-        let full_path = format!("{}/{}", home_dir, CONFIG_DIR_NAME);
-
         // make sure our config directory exists, otherwise attempt
         // to create it.
-        if !Path::new(&full_path).exists() {
-            match std::fs::create_dir(&full_path) {
+        if !Path::new(full_path).exists() {
+            match std::fs::create_dir(full_path) {
                 Ok(_) => (),
                 Err(e) => {
                     return Err(
-                        SaveError::IoError(
-                            e.to_string()
-                        )
+                        SaveError::IoError(e).into()
                     )
                 }
             }
@@ -374,9 +457,7 @@ impl Settings {
             Ok(_) => {},
             Err(e) => {
                 return Err(
-                    SaveError::IoError(
-                        e.to_string()
-                    )
+                    SaveError::IoError(e).into()
                 )
             }
         }
@@ -0,0 +1,164 @@
+// Ctrl-C handling.
+//
+// The shell itself never dies to SIGINT. When a child is running the
+// signal is forwarded to its process group, otherwise it just sets a flag
+// that long running builtins can check with `check_interrupted`. At the
+// prompt the line editor sees Ctrl-C as a key and cancels the line.
+// A job stopped with Ctrl-Z is resumed, there's no job control to bring
+// it back later.
+
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+use crate::error::UniError;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+/// the process group of the job in the foreground, or 0 for none.
+static FOREGROUND: AtomicI32 = AtomicI32::new(0);
+
+/// install the SIGINT handler, this can only be done once.
+pub fn install() -> Result<(), String> {
+    ctrlc::set_handler(|| {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+
+        #[cfg(unix)]
+        {
+            let pgid = FOREGROUND.load(Ordering::SeqCst);
+            if pgid > 0 {
+                // SAFETY: sending a signal to a process group we started.
+                unsafe {
+                    libc::kill(-pgid, libc::SIGINT);
+                }
+            }
+        }
+    })
+    .map_err(|e| format!("failed to install the Ctrl-C handler ({})", e))?;
+
+    #[cfg(unix)]
+    // SAFETY: ignoring SIGTTOU so we can take the terminal back from a
+    // child with `tcsetpgrp` while we're not in the foreground.
+    unsafe {
+        libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+    }
+
+    INSTALLED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// did `install` run? without it we can't safely hand the terminal to
+/// a child and take it back, so children stay in our process group.
+pub fn installed() -> bool {
+    INSTALLED.load(Ordering::SeqCst)
+}
+
+/// has Ctrl-C been pressed since the last `reset`?
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// forget about any earlier Ctrl-C, done before each command runs.
+pub fn reset() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+/// for builtins that can take a while, bail out if Ctrl-C was pressed.
+pub fn check_interrupted() -> Result<(), UniError> {
+    if interrupted() {
+        return Err(UniError::Custom("interrupted".to_string()));
+    }
+    Ok(())
+}
+
+/// marks a process group as the foreground job until dropped. while
+/// it's alive Ctrl-C goes to the job and it owns the terminal.
+pub struct ForegroundJob {
+    pgid: i32,
+}
+
+impl ForegroundJob {
+    pub fn new(pgid: i32) -> ForegroundJob {
+        FOREGROUND.store(pgid, Ordering::SeqCst);
+        give_terminal_to(pgid);
+        ForegroundJob { pgid }
+    }
+
+    /// wait for `pid`, a process in the job, to exit. there's no job
+    /// control to bring a stopped job back, so one stopped with Ctrl-Z
+    /// is resumed right away, after calling `stopped` with the terminal
+    /// back in our hands. otherwise we'd wait on it forever.
+    #[cfg(unix)]
+    pub fn wait(&self, pid: i32, mut stopped: impl FnMut()) -> std::io::Result<std::process::ExitStatus> {
+        use std::os::unix::process::ExitStatusExt;
+
+        loop {
+            let mut status = 0;
+            // SAFETY: `pid` is our child and nothing else waits on it.
+            if unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) } < 0 {
+                let e = std::io::Error::last_os_error();
+                if e.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+
+            if !libc::WIFSTOPPED(status) {
+                return Ok(std::process::ExitStatus::from_raw(status));
+            }
+
+            // SAFETY: getpgrp can't fail.
+            give_terminal_to(unsafe { libc::getpgrp() });
+            stopped();
+            give_terminal_to(self.pgid);
+            // SAFETY: continuing a process group we started.
+            unsafe {
+                libc::kill(-self.pgid, libc::SIGCONT);
+            }
+        }
+    }
+}
+
+impl Drop for ForegroundJob {
+    fn drop(&mut self) {
+        FOREGROUND.store(0, Ordering::SeqCst);
+
+        #[cfg(unix)]
+        // SAFETY: getpgrp can't fail.
+        give_terminal_to(unsafe { libc::getpgrp() });
+    }
+}
+
+#[cfg(unix)]
+fn give_terminal_to(pgid: i32) {
+    use std::io::IsTerminal;
+
+    if !std::io::stdin().is_terminal() {
+        return;
+    }
+
+    // SAFETY: stdin is a terminal, and a failure here just means the
+    // job doesn't get the terminal.
+    unsafe {
+        libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+    }
+}
+
+#[cfg(not(unix))]
+fn give_terminal_to(_pgid: i32) {}
+
+/// run in the child between fork and exec, puts it in its own process
+/// group and hands it the terminal so it doesn't have to wait for us.
+#[cfg(unix)]
+pub fn enter_own_process_group() -> std::io::Result<()> {
+    // SAFETY: only async-signal-safe calls, as required after fork.
+    unsafe {
+        if libc::setpgid(0, 0) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if libc::isatty(libc::STDIN_FILENO) == 1 {
+            libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+            libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+            libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+        }
+    }
+    Ok(())
+}
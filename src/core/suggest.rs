@@ -0,0 +1,207 @@
+// "did you mean ...?"
+//
+// Finds the names closest to something that was mistyped, for commands
+// that don't exist and directories that aren't there.
+
+use std::{collections::HashMap, path::Path};
+
+use super::input::HistoryEntry;
+
+/// how many suggestions to show at most.
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// how many single character edits (insert, delete, replace or swapping
+/// two neighbours) it takes to turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // rows[i][j] is the distance between a[..i] and b[..j].
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+/// the names closest to `word`, best first. `uses` says how often each
+/// name has been used, the more it has the further up it goes.
+pub fn closest<'a>(word: &str, names: impl IntoIterator<Item = &'a str>, uses: &HashMap<&str, usize>) -> Vec<&'a str> {
+    // every short name is "close" to nothing, which doesn't help anyone.
+    if word.is_empty() {
+        return Vec::new();
+    }
+
+    // anything further away than this is a different word, not a typo.
+    let limit = (word.chars().count() / 3).max(1) + 1;
+
+    let mut scored: Vec<(f64, &str)> = names
+        .into_iter()
+        .filter(|name| *name != word)
+        .filter_map(|name| {
+            let distance = edit_distance(word, name);
+            if distance > limit {
+                return None;
+            }
+            let used = uses.get(name).copied().unwrap_or(0) as f64;
+            Some((distance as f64 - (1.0 + used).ln() * 0.5, name))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(b.1)));
+    scored.into_iter().take(MAX_SUGGESTIONS).map(|(_, name)| name).collect()
+}
+
+/// how many times each command was run, going by the first word of
+/// every line in the history.
+pub fn command_uses(history: &[HistoryEntry]) -> HashMap<&str, usize> {
+    let mut uses = HashMap::new();
+    for entry in history {
+        if let Some(name) = entry.line.split_whitespace().next() {
+            *uses.entry(name).or_default() += 1;
+        }
+    }
+    uses
+}
+
+/// directories close to `wanted`, which is relative to `cwd`. the
+/// suggestions keep whatever came before the last `/`.
+pub fn closest_dirs(cwd: &str, wanted: &str) -> Vec<String> {
+    let trimmed = wanted.trim_end_matches('/');
+    let (parent, name) = match trimmed.rfind('/') {
+        Some(offset) => trimmed.split_at(offset + 1),
+        None => ("", trimmed),
+    };
+
+    let search = Path::new(cwd).join(parent);
+    let Ok(entries) = std::fs::read_dir(search) else {
+        return Vec::new();
+    };
+
+    let dirs: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+
+    closest(name, dirs.iter().map(String::as_str), &HashMap::new())
+        .into_iter()
+        .map(|dir| format!("{}{}", parent, dir))
+        .collect()
+}
+
+/// "did you mean a, b or c?", or nothing when there's nothing close.
+pub fn did_you_mean(suggestions: &[impl AsRef<str>]) -> Option<String> {
+    let names: Vec<&str> = suggestions.iter().map(AsRef::as_ref).collect();
+    match names.split_last() {
+        None => None,
+        Some((only, [])) => Some(format!("did you mean {}?", only)),
+        Some((last, rest)) => Some(format!("did you mean {} or {}?", rest.join(", "), last)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nearest<'a>(word: &str, names: &[&'a str]) -> Vec<&'a str> {
+        closest(word, names.iter().copied(), &HashMap::new())
+    }
+
+    #[test]
+    fn distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("git", "git"), 0);
+        assert_eq!(edit_distance("", "git"), 3);
+        assert_eq!(edit_distance("git", ""), 3);
+        assert_eq!(edit_distance("gt", "git"), 1, "insert");
+        assert_eq!(edit_distance("giit", "git"), 1, "delete");
+        assert_eq!(edit_distance("gut", "git"), 1, "replace");
+        assert_eq!(edit_distance("gti", "git"), 1, "swap");
+        assert_eq!(edit_distance("tgi", "git"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("sitting", "kitten"), 3);
+    }
+
+    #[test]
+    fn case_and_unicode() {
+        assert_eq!(edit_distance("Git", "git"), 1);
+        assert_eq!(edit_distance("GIT", "git"), 3);
+        assert_eq!(edit_distance("café", "cafe"), 1, "characters, not bytes");
+        assert_eq!(edit_distance("ñ", ""), 1);
+    }
+
+    #[test]
+    fn only_typos_are_close() {
+        // short words get two edits, longer ones one more per three characters.
+        assert_eq!(nearest("ls", &["lsof", "lsblk", "cat"]), ["lsof"]);
+        assert_eq!(nearest("pyhton", &["python", "pip", "pyhtonic"]), ["python", "pyhtonic"]);
+        assert_eq!(nearest("mkdri", &["mkdir", "rmdir", "make"]), ["mkdir"]);
+        assert_eq!(nearest("GREP", &["grep"]), Vec::<&str>::new());
+        assert_eq!(nearest("Grep", &["grep"]), ["grep"]);
+    }
+
+    #[test]
+    fn the_word_itself_isnt_suggested() {
+        assert_eq!(nearest("git", &["git", "gti"]), ["gti"]);
+    }
+
+    #[test]
+    fn nothing_is_close_to_nothing() {
+        assert_eq!(nearest("", &["", "ls", "cd"]), Vec::<&str>::new());
+        assert_eq!(nearest("ls", &[]), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn ties_go_alphabetically_and_only_a_few_are_kept() {
+        assert_eq!(nearest("cd", &["cx", "cp", "ca", "cb"]), ["ca", "cb", "cp"]);
+        assert_eq!(nearest("cd", &["cp", "c", "cdd"]), ["c", "cdd", "cp"]);
+    }
+
+    #[test]
+    fn commands_used_more_come_first() {
+        let names = ["grep", "perp"];
+        assert_eq!(nearest("gerp", &names), ["grep", "perp"]);
+
+        let uses = HashMap::from([("perp", 5)]);
+        assert_eq!(closest("gerp", names, &uses), ["perp", "grep"]);
+    }
+
+    #[test]
+    fn uses_come_from_the_first_word_of_each_line() {
+        let mut history = crate::core::input::History::new();
+        for line in ["git status", "git  commit", "  ls -l", "git"] {
+            history.record(line, "/");
+        }
+        let uses = command_uses(history.entries());
+        assert_eq!(uses, HashMap::from([("git", 3), ("ls", 1)]));
+    }
+
+    #[test]
+    fn did_you_mean_lists_them() {
+        assert_eq!(did_you_mean(&[] as &[&str]), None);
+        assert_eq!(did_you_mean(&["git"]).as_deref(), Some("did you mean git?"));
+        assert_eq!(did_you_mean(&["git", "gist"]).as_deref(), Some("did you mean git or gist?"));
+        assert_eq!(
+            did_you_mean(&["git".to_string(), "gist".to_string(), "gut".to_string()]).as_deref(),
+            Some("did you mean git, gist or gut?"),
+        );
+    }
+}
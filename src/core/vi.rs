@@ -0,0 +1,441 @@
+// Vi editing mode for the line editor.
+//
+// This file only knows how to turn key presses into commands and where
+// motions land, the editor itself applies them to the line.
+
+use super::editor::Key;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    Insert,
+    Normal,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Motion {
+    Left,
+    Right,
+    /// `w`, or `W` when big.
+    WordForward { big: bool },
+    /// `b`, or `B` when big.
+    WordBackward { big: bool },
+    /// `e`, or `E` when big.
+    WordEnd { big: bool },
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    /// `f`, `F`, `t` and `T`.
+    Find { ch: char, forward: bool, till: bool },
+    /// `;`, or `,` when reversed.
+    RepeatFind { reverse: bool },
+}
+
+impl Motion {
+    /// inclusive motions include the character they land on when used
+    /// with an operator, `de` deletes the last letter of the word too.
+    pub fn is_inclusive(&self) -> bool {
+        matches!(
+            self,
+            Motion::WordEnd { .. } | Motion::LineEnd | Motion::Find { .. } | Motion::RepeatFind { .. }
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Target {
+    Motion(Motion),
+    /// the whole line, from doubling the operator (`dd`, `cc`, `yy`).
+    Line,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InsertAt {
+    Cursor,
+    AfterCursor,
+    LineStart,
+    LineEnd,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Move(Motion),
+    Apply(Operator, Target),
+    Insert(InsertAt),
+    Replace(char),
+    Put { before: bool },
+    Undo,
+    Repeat,
+    HistoryPrev,
+    HistoryNext,
+}
+
+impl Action {
+    /// changes are what `.` repeats.
+    pub fn is_change(&self) -> bool {
+        match self {
+            Action::Apply(op, _) => *op != Operator::Yank,
+            Action::Insert(_) | Action::Replace(_) | Action::Put { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Command {
+    pub count: usize,
+    pub action: Action,
+}
+
+pub enum Parse {
+    /// we need more keys to know what this is.
+    Incomplete,
+    Invalid,
+    Done(Command),
+}
+
+/// what the editor keeps around while in vi mode.
+pub struct State {
+    pub mode: Mode,
+    /// keys of the normal mode command being typed.
+    pub pending: Vec<Key>,
+    /// the keys of the last change, for `.`
+    pub last_change: Vec<Key>,
+    /// the keys of the change in progress, it's only finished once
+    /// we're back in normal mode.
+    pub recording: Option<Vec<Key>>,
+    pub undo: Vec<(Vec<char>, usize)>,
+    /// the last `f`/`F`/`t`/`T`, for `;` and `,`
+    pub last_find: Option<(char, bool, bool)>,
+    pub replaying: bool,
+}
+
+impl State {
+    pub fn new() -> State {
+        State {
+            mode: Mode::Insert,
+            pending: Vec::new(),
+            last_change: Vec::new(),
+            recording: None,
+            undo: Vec::new(),
+            last_find: None,
+            replaying: false,
+        }
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// counts are capped, `99999999999w` shouldn't spin for minutes or
+/// overflow when added to the cursor.
+const MAX_COUNT: usize = 9_999;
+
+fn read_count(keys: &[Key], i: &mut usize) -> Option<usize> {
+    let mut count: Option<usize> = None;
+
+    while let Some(Key::Char(c)) = keys.get(*i) {
+        let digit = match c.to_digit(10) {
+            // a leading zero is the `0` motion, not a count.
+            Some(0) if count.is_none() => break,
+            Some(d) => d as usize,
+            None => break,
+        };
+        count = Some((count.unwrap_or(0) * 10 + digit).min(MAX_COUNT));
+        *i += 1;
+    }
+
+    count
+}
+
+enum MotionParse {
+    Incomplete,
+    Invalid,
+    Done(Motion),
+}
+
+fn parse_motion(keys: &[Key]) -> MotionParse {
+    let key = match keys.first() {
+        Some(key) => *key,
+        None => return MotionParse::Incomplete,
+    };
+
+    let motion = match key {
+        Key::Char('h') | Key::Left | Key::Backspace => Motion::Left,
+        Key::Char('l') | Key::Char(' ') | Key::Right => Motion::Right,
+        Key::Char('w') => Motion::WordForward { big: false },
+        Key::Char('W') => Motion::WordForward { big: true },
+        Key::Char('b') => Motion::WordBackward { big: false },
+        Key::Char('B') => Motion::WordBackward { big: true },
+        Key::Char('e') => Motion::WordEnd { big: false },
+        Key::Char('E') => Motion::WordEnd { big: true },
+        Key::Char('0') | Key::Home => Motion::LineStart,
+        Key::Char('^') => Motion::FirstNonBlank,
+        Key::Char('$') | Key::End => Motion::LineEnd,
+        Key::Char(';') => Motion::RepeatFind { reverse: false },
+        Key::Char(',') => Motion::RepeatFind { reverse: true },
+        Key::Char(c @ ('f' | 'F' | 't' | 'T')) => {
+            return match keys.get(1) {
+                None => MotionParse::Incomplete,
+                Some(Key::Char(ch)) => MotionParse::Done(Motion::Find {
+                    ch: *ch,
+                    forward: c == 'f' || c == 't',
+                    till: c == 't' || c == 'T',
+                }),
+                Some(_) => MotionParse::Invalid,
+            };
+        },
+        _ => return MotionParse::Invalid,
+    };
+
+    MotionParse::Done(motion)
+}
+
+/// parse the normal mode keys typed so far into a command.
+pub fn parse(keys: &[Key]) -> Parse {
+    let mut i = 0;
+    let count = read_count(keys, &mut i);
+
+    let key = match keys.get(i) {
+        Some(key) => *key,
+        None => return Parse::Incomplete,
+    };
+    i += 1;
+
+    let mut motion_count = None;
+
+    let action = match key {
+        Key::Char(c @ ('d' | 'c' | 'y')) => {
+            let op = match c {
+                'd' => Operator::Delete,
+                'c' => Operator::Change,
+                _ => Operator::Yank,
+            };
+
+            motion_count = read_count(keys, &mut i);
+            match keys.get(i) {
+                None => return Parse::Incomplete,
+                Some(Key::Char(next)) if *next == c => Action::Apply(op, Target::Line),
+                Some(_) => match parse_motion(&keys[i..]) {
+                    MotionParse::Incomplete => return Parse::Incomplete,
+                    MotionParse::Invalid => return Parse::Invalid,
+                    MotionParse::Done(motion) => Action::Apply(op, Target::Motion(motion)),
+                },
+            }
+        },
+        Key::Char('x') | Key::Delete => Action::Apply(Operator::Delete, Target::Motion(Motion::Right)),
+        Key::Char('X') => Action::Apply(Operator::Delete, Target::Motion(Motion::Left)),
+        Key::Char('s') => Action::Apply(Operator::Change, Target::Motion(Motion::Right)),
+        Key::Char('S') => Action::Apply(Operator::Change, Target::Line),
+        Key::Char('D') => Action::Apply(Operator::Delete, Target::Motion(Motion::LineEnd)),
+        Key::Char('C') => Action::Apply(Operator::Change, Target::Motion(Motion::LineEnd)),
+        Key::Char('Y') => Action::Apply(Operator::Yank, Target::Line),
+
+        Key::Char('i') => Action::Insert(InsertAt::Cursor),
+        Key::Char('a') => Action::Insert(InsertAt::AfterCursor),
+        Key::Char('I') => Action::Insert(InsertAt::LineStart),
+        Key::Char('A') => Action::Insert(InsertAt::LineEnd),
+
+        Key::Char('r') => match keys.get(i) {
+            None => return Parse::Incomplete,
+            Some(Key::Char(c)) => Action::Replace(*c),
+            Some(_) => return Parse::Invalid,
+        },
+        Key::Char('p') => Action::Put { before: false },
+        Key::Char('P') => Action::Put { before: true },
+        Key::Char('u') => Action::Undo,
+        Key::Char('.') => Action::Repeat,
+        Key::Char('k') | Key::Up | Key::Ctrl('p') => Action::HistoryPrev,
+        Key::Char('j') | Key::Down | Key::Ctrl('n') => Action::HistoryNext,
+
+        _ => match parse_motion(&keys[i - 1..]) {
+            MotionParse::Incomplete => return Parse::Incomplete,
+            MotionParse::Invalid => return Parse::Invalid,
+            MotionParse::Done(motion) => Action::Move(motion),
+        },
+    };
+
+    Parse::Done(Command {
+        count: (count.unwrap_or(1) * motion_count.unwrap_or(1)).min(MAX_COUNT),
+        action,
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Space,
+    Word,
+    Punctuation,
+}
+
+fn class(c: char, big: bool) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Space
+    }
+    else if big || c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    }
+    else {
+        CharClass::Punctuation
+    }
+}
+
+fn word_forward(buf: &[char], mut pos: usize, big: bool) -> usize {
+    if pos >= buf.len() {
+        return buf.len();
+    }
+
+    let start = class(buf[pos], big);
+    if start != CharClass::Space {
+        while pos < buf.len() && class(buf[pos], big) == start {
+            pos += 1;
+        }
+    }
+    while pos < buf.len() && class(buf[pos], big) == CharClass::Space {
+        pos += 1;
+    }
+    pos
+}
+
+fn word_backward(buf: &[char], mut pos: usize, big: bool) -> usize {
+    while pos > 0 && class(buf[pos - 1], big) == CharClass::Space {
+        pos -= 1;
+    }
+    if pos == 0 {
+        return 0;
+    }
+
+    let start = class(buf[pos - 1], big);
+    while pos > 0 && class(buf[pos - 1], big) == start {
+        pos -= 1;
+    }
+    pos
+}
+
+fn word_end(buf: &[char], mut pos: usize, big: bool) -> usize {
+    pos += 1;
+    while pos < buf.len() && class(buf[pos], big) == CharClass::Space {
+        pos += 1;
+    }
+    if pos >= buf.len() {
+        return buf.len().saturating_sub(1);
+    }
+
+    let start = class(buf[pos], big);
+    while pos + 1 < buf.len() && class(buf[pos + 1], big) == start {
+        pos += 1;
+    }
+    pos
+}
+
+/// where `cw` stops. it's `ce`, except that the word under the cursor
+/// counts even when the cursor is on its last character, so `cw` on `a`
+/// in `a b` only changes the `a`.
+pub fn change_word_end(buf: &[char], pos: usize, big: bool, count: usize) -> usize {
+    let Some(under) = buf.get(pos) else { return pos };
+
+    let mut end = pos;
+    while end + 1 < buf.len() && class(buf[end + 1], big) == class(*under, big) {
+        end += 1;
+    }
+    for _ in 1..count {
+        end = word_end(buf, end, big);
+    }
+    end
+}
+
+fn find(buf: &[char], pos: usize, ch: char, forward: bool, till: bool) -> Option<usize> {
+    if forward {
+        // `t` stops before the character, so skip the one right next to us.
+        let from = if till { pos + 2 } else { pos + 1 };
+        let found = (from..buf.len()).find(|i| buf[*i] == ch)?;
+        Some(if till { found - 1 } else { found })
+    }
+    else {
+        let to = if till { pos.saturating_sub(1) } else { pos };
+        let found = (0..to).rev().find(|i| buf[*i] == ch)?;
+        Some(if till { found + 1 } else { found })
+    }
+}
+
+/// where `motion` ends up when repeated `count` times from `pos`.
+/// `None` means the motion failed, like an `f` that found nothing.
+pub fn motion_target(
+    buf: &[char],
+    pos: usize,
+    motion: Motion,
+    count: usize,
+    last_find: Option<(char, bool, bool)>,
+) -> Option<usize> {
+    let mut target = pos;
+
+    for _ in 0..count.max(1) {
+        target = match motion {
+            Motion::Left => target.saturating_sub(1),
+            Motion::Right => (target + 1).min(buf.len()),
+            Motion::WordForward { big } => word_forward(buf, target, big),
+            Motion::WordBackward { big } => word_backward(buf, target, big),
+            Motion::WordEnd { big } => word_end(buf, target, big),
+            Motion::LineStart => 0,
+            Motion::FirstNonBlank => buf.iter().position(|c| !c.is_whitespace()).unwrap_or(0),
+            Motion::LineEnd => buf.len().saturating_sub(1),
+            Motion::Find { ch, forward, till } => find(buf, target, ch, forward, till)?,
+            Motion::RepeatFind { reverse } => {
+                let (ch, forward, till) = last_find?;
+                find(buf, target, ch, forward != reverse, till)?
+            },
+        };
+    }
+
+    Some(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(typed: &str) -> Vec<Key> {
+        typed.chars().map(Key::Char).collect()
+    }
+
+    fn count(typed: &str) -> usize {
+        match parse(&keys(typed)) {
+            Parse::Done(command) => command.count,
+            _ => panic!("\"{}\" isn't a whole command", typed),
+        }
+    }
+
+    #[test]
+    fn counts_multiply() {
+        assert_eq!(count("w"), 1);
+        assert_eq!(count("3w"), 3);
+        assert_eq!(count("2d3w"), 6);
+    }
+
+    #[test]
+    fn huge_counts_are_capped() {
+        assert_eq!(count("99999999999w"), MAX_COUNT);
+        assert_eq!(count("999999999999999999999999999999x"), MAX_COUNT);
+        assert_eq!(count("9999d9999w"), MAX_COUNT);
+    }
+
+    #[test]
+    fn change_word_stays_in_the_word_under_the_cursor() {
+        let buf: Vec<char> = "a bc, d".chars().collect();
+        assert_eq!(change_word_end(&buf, 0, false, 1), 0);
+        assert_eq!(change_word_end(&buf, 2, false, 1), 3);
+        assert_eq!(change_word_end(&buf, 3, false, 1), 3);
+        assert_eq!(change_word_end(&buf, 0, false, 2), 3);
+        assert_eq!(change_word_end(&buf, 2, true, 1), 4);
+        assert_eq!(change_word_end(&buf, 0, false, MAX_COUNT), 6);
+    }
+}
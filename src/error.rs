@@ -0,0 +1,258 @@
+// Everything that can go wrong.
+//
+// One error type for the whole terminal. Every error has a stable code
+// that scripts can match on, keeps whatever caused it as its `source`,
+// and can carry a hint on what to do about it.
+
+use std::{error::Error, fmt};
+
+use crate::core::settings::{Color, ErrorColors, WithConsoleColor};
+
+#[derive(Debug)]
+pub enum UniError {
+    NotFound(String),
+    /// a command failed with an error of its own.
+    CommandError(Box<dyn Error + Send + Sync>),
+    TooFewArguments(String),
+    /// the arguments didn't match what the command takes, this
+    /// includes its usage.
+    Usage(String),
+    IoError(std::io::Error),
+    Cd(CdError),
+    Save(SaveError),
+    Init(TerminalInitError),
+    Custom(String),
+    /// any of the above, with a hint on what to do about it. match on
+    /// `kind()` rather than the error itself so these don't get missed.
+    Hinted(Box<UniError>, String),
+}
+
+#[derive(Debug)]
+pub enum CdError {
+    /// the directory that couldn't be entered, and why.
+    FailedToSetPath(String, std::io::Error),
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    /// there's no config folder, like when `$HOME` isn't set.
+    NoSuitablePath,
+    FailedToSerialize(serde_json::Error),
+    IoError(std::io::Error),
+}
+
+#[derive(Debug)]
+pub enum TerminalInitError {
+    CantGetDirectory(std::io::Error),
+}
+
+impl UniError {
+    /// the error under any hint, `matches!(e.kind(), UniError::NotFound(_))`
+    /// works whether or not it has one.
+    pub fn kind(&self) -> &UniError {
+        match self {
+            UniError::Hinted(e, _) => e.kind(),
+            e => e,
+        }
+    }
+
+    /// what kind of error this is. these never change, so they're safe
+    /// to match on.
+    pub fn code(&self) -> &'static str {
+        match self.kind() {
+            UniError::NotFound(_) => "not-found",
+            UniError::CommandError(_) => "command",
+            UniError::TooFewArguments(_) => "too-few-arguments",
+            UniError::Usage(_) => "usage",
+            UniError::IoError(_) => "io",
+            UniError::Cd(CdError::FailedToSetPath(..)) => "cd-failed",
+            UniError::Save(SaveError::NoSuitablePath) => "save-no-path",
+            UniError::Save(SaveError::FailedToSerialize(_)) => "save-serialize",
+            UniError::Save(SaveError::IoError(_)) => "save-io",
+            UniError::Init(TerminalInitError::CantGetDirectory(_)) => "init-no-cwd",
+            UniError::Custom(_) => "failed",
+            UniError::Hinted(..) => unreachable!("kind() looks through hints"),
+        }
+    }
+
+    pub fn hint(&self) -> Option<&str> {
+        match self {
+            UniError::Hinted(_, hint) => Some(hint),
+            _ => None,
+        }
+    }
+
+    /// attach a hint, nothing changes when there isn't one.
+    pub fn with_hint(self, hint: Option<String>) -> UniError {
+        match hint {
+            Some(hint) => UniError::Hinted(Box::new(self), hint),
+            None => self,
+        }
+    }
+
+    /// the error, what caused it and the hint, one per line. `colors` is
+    /// `None` for plain text.
+    pub fn render(&self, colors: Option<&ErrorColors>) -> String {
+        let paint = |text: String, pick: fn(&ErrorColors) -> &Color| match colors {
+            Some(colors) => text.rgb(pick(colors)),
+            None => text,
+        };
+
+        let mut rendered = format!("{}: {}", paint(format!("error[{}]", self.code()), |c| &c.error), self);
+
+        let mut source = self.source();
+        while let Some(cause) = source {
+            rendered.push_str(&format!("\n  caused by: {}", cause));
+            source = cause.source();
+        }
+
+        if let Some(hint) = self.hint() {
+            rendered.push_str(&format!("\n  {} {}", paint("hint:".to_string(), |c| &c.hint), hint));
+        }
+
+        rendered
+    }
+}
+
+impl fmt::Display for UniError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind() {
+            UniError::NotFound(info) => write!(f, "{}", info),
+            UniError::CommandError(e) => write!(f, "{}", e),
+            UniError::TooFewArguments(msg) => write!(f, "not enough arguments: {}", msg),
+            UniError::Usage(msg) => write!(f, "{}", msg),
+            UniError::IoError(e) => write!(f, "{}", e),
+            UniError::Cd(e) => write!(f, "{}", e),
+            UniError::Save(e) => write!(f, "{}", e),
+            UniError::Init(e) => write!(f, "{}", e),
+            UniError::Custom(s) => write!(f, "{}", s),
+            UniError::Hinted(..) => unreachable!("kind() looks through hints"),
+        }
+    }
+}
+
+// the wrapped errors are shown as this one, so the chain carries on
+// from what caused them.
+impl Error for UniError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind() {
+            UniError::CommandError(e) => e.source(),
+            UniError::IoError(e) => e.source(),
+            UniError::Cd(e) => e.source(),
+            UniError::Save(e) => e.source(),
+            UniError::Init(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CdError::FailedToSetPath(dir, _) => {
+                write!(f, "failed to set working directory to \"{}\"", dir)
+            }
+        }
+    }
+}
+
+impl Error for CdError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CdError::FailedToSetPath(_, e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::NoSuitablePath => write!(f, "no suitable path to save to"),
+            SaveError::FailedToSerialize(_) => write!(f, "failed to serialize the settings"),
+            SaveError::IoError(_) => write!(f, "failed to write the settings"),
+        }
+    }
+}
+
+impl Error for SaveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SaveError::NoSuitablePath => None,
+            SaveError::FailedToSerialize(e) => Some(e),
+            SaveError::IoError(e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for TerminalInitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TerminalInitError::CantGetDirectory(_) => write!(f, "can't get the current directory"),
+        }
+    }
+}
+
+impl Error for TerminalInitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TerminalInitError::CantGetDirectory(e) => Some(e),
+        }
+    }
+}
+
+/// lets `writeln!(io.stdout, ..)?` be used in commands.
+impl From<std::io::Error> for UniError {
+    fn from(e: std::io::Error) -> Self {
+        UniError::IoError(e)
+    }
+}
+
+impl From<CdError> for UniError {
+    fn from(e: CdError) -> Self {
+        UniError::Cd(e)
+    }
+}
+
+impl From<SaveError> for UniError {
+    fn from(e: SaveError) -> Self {
+        UniError::Save(e)
+    }
+}
+
+impl From<TerminalInitError> for UniError {
+    fn from(e: TerminalInitError) -> Self {
+        UniError::Init(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_dont_change_the_kind() {
+        let plain = UniError::NotFound("nope".to_string());
+        assert!(matches!(plain.kind(), UniError::NotFound(_)));
+
+        let hinted = UniError::NotFound("nope".to_string()).with_hint(Some("try yes".to_string()));
+        assert!(matches!(hinted.kind(), UniError::NotFound(name) if name == "nope"));
+        assert_eq!(hinted.code(), "not-found");
+        assert_eq!(hinted.hint(), Some("try yes"));
+        assert_eq!(hinted.to_string(), "nope");
+    }
+
+    #[test]
+    fn render_shows_the_cause_and_hint() {
+        let e = UniError::from(CdError::FailedToSetPath(
+            "x".to_string(),
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no such directory"),
+        ))
+        .with_hint(Some("did you mean `y`?".to_string()));
+
+        assert_eq!(e.render(None), concat!(
+            "error[cd-failed]: failed to set working directory to \"x\"\n",
+            "  caused by: no such directory\n",
+            "  hint: did you mean `y`?",
+        ));
+    }
+}
@@ -0,0 +1,14 @@
+//! A shell that can be embedded.
+//!
+//! `TerminalBuilder` puts a `Terminal` together and `Terminal::run_line`
+//! runs a line, returning what it printed and its status.
+
+pub mod core;
+pub mod commands;
+pub mod error;
+#[macro_use]
+pub mod logger;
+
+pub use crate::core::{builder::TerminalBuilder, session::Session, LineOutput, Terminal};
+pub use crate::commands::{Cmd, Context, Outcome};
+pub use crate::error::UniError;
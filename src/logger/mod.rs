@@ -3,7 +3,7 @@ use std::{self, io::Error, fs::File};
 // TODO: add file logging instruments, for a concrete logging system
 //       that can be referenced for internal errors.
 
-const LOG_FILE: &'static str = "term_log.txt";
+const LOG_FILE: &str = "term_log.txt";
 
 use crate::core::settings::{CONFIG_PATH_DIR_ENVVAR, CONFIG_DIR_NAME};
 use std::fs::OpenOptions;
@@ -26,7 +26,6 @@ pub fn find_log_location() -> Option<String> {
 pub fn build_file(base_dir: &String) -> Result<File, Error> {
     let path = format!("{base_dir}/{LOG_FILE}");
     OpenOptions::new()
-        .write(true)
         .append(true)
         .create(true)
         .open(path)
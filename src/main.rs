@@ -1,18 +1,56 @@
-pub mod core;
-pub mod commands;
-#[macro_use]
-pub mod logger;
+use std::io::IsTerminal;
 
-use crate::core::*;
+use terminal_rs::core::{settings, Terminal};
+
+const USAGE: &str = "usage: terminal-rs [--timing] [-c \"command; command\"]";
 
 fn main() {
-    match Terminal::new() {
-        Ok(inst) => {
-            inst.execute();
+    let mut command = None;
+    let mut timing = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // runs its argument and exits, like `sh -c`.
+            "-c" => match args.next() {
+                Some(arg) => command = Some(arg),
+                None => {
+                    eprintln!("ERROR: -c expects a command.\n{}", USAGE);
+                    std::process::exit(2);
+                }
+            },
+            "--timing" => timing = true,
+            other => {
+                eprintln!("ERROR: unknown argument \"{}\"\n{}", other, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let interactive = command.is_none() && std::io::stdin().is_terminal();
+    let terminal = if interactive {
+        Terminal::new()
+    }
+    else {
+        Terminal::non_interactive()
+    };
+
+    match terminal {
+        Ok(mut inst) => {
+            if timing {
+                eprintln!("startup took {:.2?}", inst.startup_time());
+            }
+
+            match command {
+                Some(command) => inst.execute_script(command.as_bytes()),
+                None if interactive => inst.execute(),
+                // commands piped into us.
+                None => inst.execute_script(std::io::stdin().lock()),
+            }
         },
         Err(e) => {
-            let repr: String = e.into();
-            eprintln!("ERROR: {}", repr);
+            eprintln!("{}", e.render(interactive.then_some(&settings::ErrorColors::default())));
+            std::process::exit(1);
         }
     }
 }
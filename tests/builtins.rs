@@ -0,0 +1,409 @@
+// Every builtin in commands/impls.rs, run through a scripted session.
+//
+// The stubs are sh scripts, so this only runs on unix.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+
+use common::{example, example_library, path_str, write_executable, Session, RPC_PLUGIN};
+use terminal_rs::commands::plugin::PLUGIN_EXTENSION;
+use terminal_rs::core::settings::CONFIG_DIR_NAME;
+
+#[test]
+fn help() {
+    let mut session = Session::new();
+
+    let all = session.ok("help");
+    assert!(all.contains("cd - change the working directory."));
+    assert!(all.contains("greet - No description"));
+
+    let builtins = session.ok("help -b");
+    assert!(builtins.contains("history - view your command history"));
+    assert!(!builtins.contains("greet"));
+
+    assert_eq!(session.ok("help cd"), "cd - change the working directory.\nusage: cd <dir>\n");
+
+    let missing = session.fails("help nope", 1);
+    assert!(missing.stderr.starts_with("error[not-found]: the command \"nope\" does not exist."));
+}
+
+#[test]
+fn history() {
+    let mut session = Session::new();
+    session.ok("greet one");
+    session.fails("complain", 3);
+    session.ok("greet two");
+
+    let failed = session.ok("history --failed");
+    assert!(failed.contains("complain"));
+    assert!(!failed.contains("greet"));
+
+    // the history command is recorded before it runs.
+    let last = session.ok("history -n 1");
+    assert_eq!(last.lines().count(), 1);
+    assert!(last.contains("history -n 1"));
+
+    let json: serde_json::Value = serde_json::from_str(&session.ok("history --grep greet --json")).unwrap();
+    let entries = json.as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0]["number"], 1);
+    assert_eq!(entries[0]["line"], "greet one");
+    assert_eq!(entries[0]["status"], 0);
+    assert_eq!(entries[0]["cwd"], path_str(&session.cwd()));
+
+    session.ok("history -d 1");
+    let json: serde_json::Value = serde_json::from_str(&session.ok("history --json")).unwrap();
+    assert_eq!(json[0]["line"], "complain");
+
+    session.ok("history -c");
+    assert_eq!(session.ok("history").lines().count(), 1);
+
+    let missing = session.fails("history -d 99", 1);
+    assert!(missing.stderr.contains("there is no history entry 99."));
+    let usage = session.fails("history -n", 1);
+    assert!(usage.stderr.starts_with("error[usage]: history: -n expects N"));
+}
+
+#[test]
+fn cd() {
+    let mut session = Session::new();
+    fs::create_dir(session.cwd().join("sub")).unwrap();
+
+    session.ok("cd sub");
+    assert_eq!(session.terminal.current_path(), path_str(&session.cwd().join("sub")));
+    session.ok("cd ..");
+    assert_eq!(session.terminal.current_path(), path_str(&session.cwd()));
+    let sub = path_str(&session.cwd().join("sub"));
+    session.ok(&format!("cd {}", sub));
+    assert_eq!(session.terminal.current_path(), sub);
+    session.ok("cd ..");
+    // only the session moves, not the process.
+    assert_ne!(path_str(&std::env::current_dir().unwrap()), sub);
+
+    let missing = session.fails("cd su", 1);
+    assert!(missing.stderr.starts_with("error[cd-failed]: failed to set working directory to \"su\""));
+    assert!(missing.stderr.contains("caused by: No such file or directory"));
+    assert!(missing.stderr.contains("hint: did you mean sub?"));
+    assert_eq!(session.terminal.current_path(), path_str(&session.cwd()));
+}
+
+#[test]
+fn ls() {
+    let mut session = Session::new();
+    fs::write(session.cwd().join("notes.txt"), "").unwrap();
+    fs::create_dir(session.cwd().join("docs")).unwrap();
+    fs::write(session.cwd().join("docs").join("inner.txt"), "").unwrap();
+
+    let listing = session.ok("ls");
+    assert!(listing.starts_with("* Executable * Directory * File\n"));
+    assert!(listing.contains("notes.txt"));
+    assert!(listing.contains("docs"));
+    assert!(!listing.contains("inner.txt"));
+
+    let filtered = session.ok("ls -f -F");
+    assert!(!filtered.contains("notes.txt"));
+    assert!(filtered.contains("(*)"));
+    assert!(filtered.contains("docs"));
+
+    assert!(session.ok("ls docs").contains("inner.txt"));
+
+    let recursive = session.ok("ls -R");
+    assert!(recursive.contains(&format!("{}:", path_str(&session.cwd().join("docs")))));
+    assert!(recursive.contains("inner.txt"));
+
+    let missing = session.fails("ls doc", 1);
+    assert!(missing.stderr.starts_with("error[not-found]: there is no directory \"doc\"."));
+    assert!(missing.stderr.contains("hint: did you mean docs?"));
+}
+
+#[test]
+fn man() {
+    let mut session = Session::new();
+
+    let docs = session.ok("man cd");
+    assert!(docs.starts_with("( documentation for cd )"));
+    assert!(docs.contains("usage: cd <dir>"));
+    assert!(docs.contains("change the working directory."));
+
+    assert!(session.ok("man greet").contains("This command is a file located on the filesystem."));
+
+    let usage = session.fails("man", 1);
+    assert!(usage.stderr.starts_with("error[usage]: man:"));
+    assert!(usage.stderr.contains("hint: see `man man` for more."));
+}
+
+#[test]
+fn exit() {
+    {
+        let mut session = Session::new();
+        assert_eq!(session.terminal.exit_status(), None);
+        session.ok("exit 3");
+        assert_eq!(session.terminal.exit_status(), Some(3));
+    }
+
+    // a plain exit leaves with the status of the command before it.
+    {
+        let mut session = Session::new();
+        let output = session.run("complain; exit");
+        assert_eq!(output.stderr, "something went wrong\ncomplain exited with status (3)\n");
+        assert_eq!(session.terminal.exit_status(), Some(3));
+    }
+
+    {
+        let mut session = Session::new();
+        let usage = session.fails("exit soon", 1);
+        assert!(usage.stderr.starts_with("error[usage]: exit:"));
+        assert_eq!(session.terminal.exit_status(), None);
+    }
+}
+
+#[test]
+fn rmdir() {
+    let mut session = Session::new();
+    fs::create_dir(session.cwd().join("empty")).unwrap();
+    fs::create_dir(session.cwd().join("full")).unwrap();
+    fs::write(session.cwd().join("full").join("file"), "").unwrap();
+
+    session.ok("rmdir empty");
+    assert!(!session.cwd().join("empty").exists());
+
+    let not_empty = session.fails("rmdir full", 1);
+    assert!(not_empty.stderr.starts_with("failed to remove folder:"));
+    assert!(session.cwd().join("full").exists());
+
+    session.ok("rmdir -f full");
+    assert!(!session.cwd().join("full").exists());
+
+    // `--` lets a directory start with a dash.
+    fs::create_dir(session.cwd().join("-odd")).unwrap();
+    session.ok("rmdir -- -odd");
+    assert!(!session.cwd().join("-odd").exists());
+}
+
+#[test]
+fn mkdir() {
+    let mut session = Session::new();
+
+    session.ok("mkdir made");
+    assert!(session.cwd().join("made").is_dir());
+
+    let exists = session.fails("mkdir made", 1);
+    assert!(exists.stderr.starts_with("failed to create directory:"));
+
+    let absolute = session.cwd().join("absolute");
+    session.ok(&format!("mkdir {}", path_str(&absolute)));
+    assert!(absolute.is_dir());
+}
+
+#[test]
+fn cfg() {
+    let mut session = Session::new();
+
+    session.ok("cfg 1 2 3 -cP");
+    session.ok("cfg 4 5 6 -cC");
+    session.ok("cfg 7 8 9 -cK");
+    session.ok("cfg off -E");
+    session.ok("cfg vi -M");
+    session.ok("cfg 3 -I");
+    session.ok("cfg .sh -X");
+    session.ok("cfg always -R");
+    session.ok("cfg on -Y");
+
+    let settings = session.settings();
+    assert_eq!(settings["path_color"], serde_json::json!({ "r": 1, "g": 2, "b": 3 }));
+    assert_eq!(settings["highlight"]["command"], serde_json::json!({ "r": 4, "g": 5, "b": 6 }));
+    assert_eq!(settings["errors"]["error"], serde_json::json!({ "r": 7, "g": 8, "b": 9 }));
+    assert_eq!(settings["history_expansion"], false);
+    assert_eq!(settings["edit_mode"], "vi");
+    assert_eq!(settings["ignore_eof"], 3);
+    assert_eq!(settings["path_extensions"], serde_json::json!([".sh"]));
+    assert_eq!(settings["report_status"], "always");
+    assert_eq!(settings["autocorrect"], true);
+
+    // the extension can be left off now, and every status is reported.
+    session.stub("tool.sh", "echo tool");
+    let output = session.run("tool");
+    assert_eq!(output.stdout, "tool\n");
+    assert_eq!(output.stderr, "tool exited with status (0)\n");
+
+    // what was saved is what the next session starts with.
+    session.restart();
+    let settings = session.settings();
+    assert_eq!(settings["edit_mode"], "vi");
+    assert_eq!(settings["path_extensions"], serde_json::json!([".sh"]));
+
+    session.ok("cfg none -X");
+    session.ok("cfg never -R");
+    assert_eq!(session.settings()["path_extensions"], serde_json::json!([]));
+
+    let missing = session.fails("cfg", 1);
+    assert!(missing.stderr.starts_with("error[too-few-arguments]:"));
+    let switch = session.fails("cfg maybe -Y", 1);
+    assert!(switch.stderr.contains("expected \"on\" or \"off\", got \"maybe\""));
+    let color = session.fails("cfg 1 2 -cP", 1);
+    assert!(color.stderr.contains("expected value for color but got nothing."));
+    let unknown = session.fails("cfg 1 -Q", 1);
+    assert!(unknown.stderr.starts_with("error[usage]: cfg: unknown flag \"-Q\""));
+    session.fails("cfg 1", 1);
+}
+
+#[test]
+fn cfg_not_found_handler() {
+    let mut session = Session::new();
+    let handler = session.stub("handler", "echo \"fallback for $*\"");
+    session.stub("suggester", "echo \"try installing $1\" >&2\nexit 127");
+
+    session.ok(&format!("cfg {} -W", path_str(&handler)));
+    assert_eq!(session.settings()["not_found_handler"], path_str(&handler));
+    assert_eq!(session.ok("missing a b"), "fallback for missing a b\n");
+
+    // a handler that fails falls back to the usual error.
+    session.ok("cfg suggester -W");
+    let output = session.fails("missing", 1);
+    assert!(output.stderr.starts_with("try installing missing\nerror[not-found]: the command missing does not exist."));
+
+    session.ok("cfg none -W");
+    assert_eq!(session.settings()["not_found_handler"], serde_json::Value::Null);
+    let output = session.fails("missing", 1);
+    assert!(output.stderr.starts_with("error[not-found]"));
+}
+
+#[test]
+fn clear() {
+    let mut session = Session::new();
+    assert_eq!(session.ok("clear"), "\x1B[2J");
+    session.fails("clear everything", 1);
+}
+
+#[test]
+fn r#where() {
+    let mut session = Session::new();
+
+    assert_eq!(session.ok("where cd"), "cd: this command is builtin\n");
+    assert_eq!(session.ok("where greet"), format!("greet: {}\n", path_str(&session.bin().join("greet"))));
+
+    // a builtin wins over PATH, -a shows both.
+    session.stub("pwd", "echo fake");
+    assert_eq!(session.ok("where pwd"), "pwd: this command is builtin\n");
+    assert_eq!(
+        session.ok("where -a pwd"),
+        format!("pwd: this command is builtin\npwd: {}\n", path_str(&session.bin().join("pwd"))),
+    );
+
+    assert_eq!(session.fails("where nope", 1).stdout, "no such command \"nope\"\n");
+}
+
+#[test]
+fn pwd() {
+    let mut session = Session::new();
+    let cwd = path_str(&session.cwd());
+    // the process stays wherever the tests were started.
+    let sys = path_str(&std::env::current_dir().unwrap());
+    assert_eq!(session.ok("pwd"), format!("locally: {}\nsys: {}\n", cwd, sys));
+    session.fails("pwd -x", 1);
+}
+
+#[test]
+fn rehash() {
+    let mut session = Session::new();
+    session.ok("greet");
+
+    let output = session.ok("rehash");
+    assert!(output.starts_with("read 1 directories ("));
+    assert!(output.contains(" commands) in "));
+
+    session.stub("fresh", "echo fresh");
+    session.ok("rehash");
+    assert_eq!(session.ok("fresh"), "fresh\n");
+}
+
+#[test]
+fn plugin() {
+    let mut session = Session::new();
+    assert_eq!(session.ok("plugin list"), "no plugins are loaded.\n");
+
+    write_executable(&session.cwd().join("waver"), RPC_PLUGIN);
+    session.ok("plugin load ./waver");
+    let listed = session.ok("plugin list");
+    assert!(listed.starts_with("waver 0.1.0 ("));
+    assert!(listed.contains("  commands: wave"));
+    assert_eq!(session.ok("wave"), "o/\n");
+
+    session.ok("plugin unload waver");
+    assert_eq!(session.ok("plugin list"), "no plugins are loaded.\n");
+    assert_eq!(session.fails("wave", 1).stdout, "");
+
+    // plugins in the config folder are loaded at startup.
+    let folder = session.home().join(CONFIG_DIR_NAME).join("plugins");
+    fs::create_dir_all(&folder).unwrap();
+    write_executable(&folder.join("waver"), RPC_PLUGIN);
+    session.restart();
+    assert!(session.ok("plugin list").starts_with("waver 0.1.0 ("));
+    assert_eq!(session.ok("wave"), "o/\n");
+
+    // and a plain name loads one from there.
+    session.ok("plugin unload waver");
+    session.ok("plugin load waver");
+    assert_eq!(session.ok("wave"), "o/\n");
+    let no_library = session.fails("plugin load nope", 1);
+    assert!(no_library.stderr.contains(&format!("nope.{}", PLUGIN_EXTENSION)), "{:?}", no_library);
+
+    let missing = session.fails("plugin unload nope", 1);
+    assert!(missing.stderr.starts_with("error[not-found]: no plugin called \"nope\" is loaded."));
+    let no_target = session.fails("plugin load", 1);
+    assert!(no_target.stderr.starts_with("error[too-few-arguments]:"));
+    let action = session.fails("plugin explode", 1);
+    assert!(action.stderr.starts_with("error[usage]: plugin:"));
+}
+
+#[test]
+fn sample_rpc_plugin() {
+    let mut session = Session::new();
+    session.ok(&format!("plugin load {}", path_str(&example("rpc_plugin"))));
+
+    assert!(session.ok("plugin list").starts_with("sample "));
+    assert_eq!(session.ok("shout hi there"), "HI THERE!\n");
+    assert_eq!(session.ok("getenv HOME"), format!("{}\n", path_str(&session.home())));
+    assert!(session.fails("getenv NOPE_NOT_SET", 1).stderr.starts_with("no such variable\n"));
+}
+
+#[test]
+fn sample_native_plugin() {
+    let mut session = Session::new();
+    let library = path_str(&example_library("native_plugin"));
+    session.ok(&format!("plugin load {}", library));
+    assert!(session.ok("plugin list").starts_with(&format!("native 0.2.0 ({})", library)));
+
+    assert_eq!(session.ok("greet ann bob"), "hello, ann\nhello, bob\n");
+    assert_eq!(session.ok("here"), format!("{}\n", path_str(&session.cwd())));
+    let grumble = session.fails("grumble", 4);
+    assert_eq!(grumble.stdout, "");
+    assert!(grumble.stderr.starts_with("grr\n"), "{:?}", grumble);
+
+    // plugins win over PATH, but not over builtins.
+    assert_eq!(
+        session.ok("where -a greet"),
+        format!("greet: {}\ngreet: {}\n", library, path_str(&session.bin().join("greet"))),
+    );
+
+    session.ok("plugin unload native");
+    assert_eq!(session.ok("greet ann"), "hello ann\n");
+    session.ok(&format!("plugin load {}", library));
+    assert_eq!(session.ok("greet ann"), "hello, ann\n");
+}
+
+#[test]
+fn broken_native_plugins() {
+    let mut session = Session::new();
+    let newer = session.fails(&format!("plugin load {}", path_str(&example_library("newer_plugin"))), 1);
+    assert!(newer.stderr.contains("it was built for plugin api v2, but this is v1"), "{:?}", newer);
+
+    let fake = session.cwd().join(format!("fake.{}", PLUGIN_EXTENSION));
+    fs::write(&fake, "not a library").unwrap();
+    let fake = session.fails(&format!("plugin load {}", path_str(&fake)), 1);
+    assert!(fake.stderr.contains("failed to load plugin"), "{:?}", fake);
+    assert_eq!(session.ok("plugin list"), "no plugins are loaded.\n");
+}
@@ -0,0 +1,182 @@
+// Scripted sessions against a real `Terminal`.
+//
+// Every session gets its own HOME, working directory and PATH folder of
+// stub executables, all inside a temporary folder that's removed once
+// the session is dropped. Nothing a session does touches the process,
+// so they can all run at once.
+
+#![cfg(unix)]
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use terminal_rs::{
+    core::settings::{CONFIG_DIR_NAME, SETTINGS_FILE_NAME},
+    LineOutput, Terminal, TerminalBuilder,
+};
+
+static SESSIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// what every session's PATH starts out with.
+const STUBS: &[(&str, &str)] = &[
+    ("greet", "echo \"hello $*\""),
+    ("complain", "echo \"something went wrong\" >&2\nexit 3"),
+];
+
+/// a plugin written in sh that speaks just enough json-rpc to be loaded
+/// and run `wave`.
+pub const RPC_PLUGIN: &str = r#"#!/bin/sh
+while read -r line; do
+    id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+    case "$line" in
+        *'"describe"'*)
+            printf '%s\n' '{"jsonrpc":"2.0","id":'"$id"',"result":{"name":"waver","version":"0.1.0","commands":[{"name":"wave","desc":"wave hello"}]}}'
+            ;;
+        *'"command":"wave"'*)
+            printf '%s\n' '{"jsonrpc":"2.0","method":"output","params":{"stream":"stdout","data":"o/\n"}}'
+            printf '%s\n' '{"jsonrpc":"2.0","id":'"$id"',"result":{"status":0}}'
+            ;;
+        *'"shutdown"'*)
+            exit 0
+            ;;
+    esac
+done
+"#;
+
+pub struct Session {
+    pub terminal: Terminal,
+    root: PathBuf,
+    /// give the terminal an empty stdin, rather than the process's own.
+    inject_stdin: bool,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Self::create(true)
+    }
+
+    /// a session that reads the process's stdin, like an embedder that
+    /// only captures the output.
+    pub fn without_stdin() -> Session {
+        Self::create(false)
+    }
+
+    fn create(inject_stdin: bool) -> Session {
+        let root = std::env::temp_dir().join(format!(
+            "term-rs-session-{}-{}",
+            std::process::id(),
+            SESSIONS.fetch_add(1, Ordering::SeqCst),
+        ));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["home", "cwd", "bin"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        let terminal = Self::build(&root, inject_stdin);
+        let session = Session { terminal, root, inject_stdin };
+        for (name, script) in STUBS {
+            session.stub(name, script);
+        }
+        session
+    }
+
+    fn build(root: &Path, inject_stdin: bool) -> Terminal {
+        let builder = TerminalBuilder::new()
+            // children see the temporary HOME too.
+            .env("HOME", path_str(&root.join("home")))
+            .config_folder(path_str(&root.join("home").join(CONFIG_DIR_NAME)))
+            .current_dir(path_str(&root.join("cwd")))
+            .scan_path(false)
+            .path_folder(path_str(&root.join("bin")));
+        let builder = match inject_stdin {
+            true => builder.streams(std::io::empty(), std::io::sink(), std::io::sink()),
+            false => builder,
+        };
+        builder.build().unwrap()
+    }
+
+    /// start a new terminal in the same HOME, cwd and PATH, like
+    /// opening the shell again.
+    pub fn restart(&mut self) {
+        self.terminal = Self::build(&self.root, self.inject_stdin);
+    }
+
+    pub fn home(&self) -> PathBuf {
+        self.root.join("home")
+    }
+
+    pub fn cwd(&self) -> PathBuf {
+        self.root.join("cwd")
+    }
+
+    pub fn bin(&self) -> PathBuf {
+        self.root.join("bin")
+    }
+
+    /// an executable sh script called `name` in PATH.
+    pub fn stub(&self, name: &str, script: &str) -> PathBuf {
+        write_executable(&self.bin().join(name), &format!("#!/bin/sh\n{}\n", script))
+    }
+
+    pub fn run(&mut self, line: &str) -> LineOutput {
+        self.terminal.run_line(line)
+    }
+
+    /// run `line`, which has to work without complaining. returns what
+    /// it printed.
+    pub fn ok(&mut self, line: &str) -> String {
+        let output = self.run(line);
+        assert_eq!(output.status, 0, "`{}` failed: {:?}", line, output);
+        assert_eq!(output.stderr, "", "`{}` printed errors: {:?}", line, output);
+        output.stdout
+    }
+
+    /// run `line`, which has to fail with `status`.
+    pub fn fails(&mut self, line: &str, status: i32) -> LineOutput {
+        let output = self.run(line);
+        assert_eq!(output.status, status, "`{}` should have failed: {:?}", line, output);
+        output
+    }
+
+    /// save the settings, like exiting would, and read back the json.
+    pub fn settings(&self) -> serde_json::Value {
+        self.terminal.settings().save().unwrap();
+        let path = self.home().join(CONFIG_DIR_NAME).join(SETTINGS_FILE_NAME);
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+pub fn write_executable(path: &Path, contents: &str) -> PathBuf {
+    fs::write(path, contents).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_path_buf()
+}
+
+/// one of the examples, `cargo test` builds them next to the tests.
+pub fn example(name: &str) -> PathBuf {
+    let deps = std::env::current_exe().unwrap();
+    let path = deps.parent().unwrap().parent().unwrap().join("examples").join(name);
+    assert!(path.is_file(), "{} hasn't been built, run `cargo build --examples`", path.display());
+    path
+}
+
+/// one of the examples that's built as a shared library.
+pub fn example_library(name: &str) -> PathBuf {
+    let file = format!("{}{}{}", std::env::consts::DLL_PREFIX, name, std::env::consts::DLL_SUFFIX);
+    example(&file)
+}
+
+pub fn path_str(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
@@ -0,0 +1,171 @@
+// Whole lines through a scripted session: operators, statuses, errors
+// and what ends up on which stream.
+//
+// The stubs are sh scripts, so this only runs on unix.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::Session;
+
+#[test]
+fn statuses_and_streams() {
+    let mut session = Session::new();
+
+    let output = session.fails("complain", 3);
+    assert_eq!(output.stdout, "");
+    assert_eq!(output.stderr, "something went wrong\ncomplain exited with status (3)\n");
+
+    let output = session.run("greet a; greet b");
+    assert_eq!(output.stdout, "hello a\nhello b\n");
+    assert_eq!(output.status, 0);
+}
+
+#[test]
+fn and_or() {
+    let mut session = Session::new();
+
+    assert_eq!(session.ok("greet a && greet b"), "hello a\nhello b\n");
+    assert_eq!(session.fails("complain && greet b", 3).stdout, "");
+    assert_eq!(session.run("complain || greet b").stdout, "hello b\n");
+    assert_eq!(session.ok("greet a || greet b"), "hello a\n");
+    assert_eq!(session.run("complain; greet b").status, 0);
+}
+
+#[test]
+fn unknown_commands() {
+    let mut session = Session::new();
+
+    let output = session.fails("gret", 1);
+    assert_eq!(output.stderr, "error[not-found]: the command gret does not exist.\n  hint: did you mean greet?\n");
+
+    let output = session.fails("zzzzzzzz", 1);
+    assert_eq!(output.stderr, "error[not-found]: the command zzzzzzzz does not exist.\n");
+}
+
+#[test]
+fn comments_and_blank_lines() {
+    let mut session = Session::new();
+
+    assert_eq!(session.ok("# greet"), "");
+    assert_eq!(session.ok("   "), "");
+    // neither ends up in the history, only `history` itself does.
+    let history = session.ok("history");
+    assert_eq!(history.lines().count(), 1);
+    assert!(history.trim_end().ends_with("history"));
+}
+
+#[test]
+fn external_commands_without_stdin() {
+    use std::{fs::File, os::fd::FromRawFd};
+
+    // stdin becomes a pipe nobody writes to, reading it blocks until the
+    // write end is dropped.
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for both ends, and fd 0 is put back below.
+    let saved = unsafe {
+        assert_eq!(libc::pipe(fds.as_mut_ptr()), 0);
+        let saved = libc::dup(0);
+        libc::dup2(fds[0], 0);
+        libc::close(fds[0]);
+        saved
+    };
+    // SAFETY: nothing else owns the write end.
+    let writer = unsafe { File::from_raw_fd(fds[1]) };
+    let (done, finished) = std::sync::mpsc::channel::<()>();
+    let watchdog = std::thread::spawn(move || {
+        let _ = finished.recv_timeout(Duration::from_secs(5));
+        drop(writer);
+    });
+
+    let mut session = Session::without_stdin();
+    let started = Instant::now();
+    let output = session.run("greet a");
+    let took = started.elapsed();
+
+    drop(done);
+    watchdog.join().unwrap();
+    // SAFETY: `saved` is the original stdin.
+    unsafe {
+        libc::dup2(saved, 0);
+        libc::close(saved);
+    }
+
+    assert_eq!(output.stdout, "hello a\n");
+    assert!(took < Duration::from_secs(5), "waited {:?} for stdin", took);
+}
+
+#[test]
+fn colors_are_per_terminal() {
+    let mut plain = Session::new();
+    let mut colored = Session::new();
+    colored.terminal.session_mut().settings.colors = true;
+
+    assert!(!plain.fails("gret", 1).stderr.contains('\x1b'));
+    assert!(colored.fails("gret", 1).stderr.contains('\x1b'));
+    // building another one doesn't change the first.
+    plain.restart();
+    assert!(colored.fails("gret", 1).stderr.contains('\x1b'));
+}
+
+#[test]
+fn a_command_killed_by_ctrl_c_stops_the_line() {
+    let mut session = Session::new();
+    session.stub("interrupted", "kill -INT $$");
+
+    let output = session.run("interrupted; greet after");
+    assert_eq!(output.stdout, "");
+    assert_eq!(output.status, 128 + libc::SIGINT);
+
+    // not even `||` runs, the whole line was interrupted.
+    assert_eq!(session.run("interrupted || greet next").stdout, "");
+    assert_eq!(session.ok("greet next"), "hello next\n");
+}
+
+#[test]
+fn environment_that_isnt_utf8() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt, process::Command};
+
+    let session = Session::new();
+    let output = Command::new(env!("CARGO_BIN_EXE_terminal-rs"))
+        .args(["-c", "sh -c 'printf %s \"$BAD\" | od -An -tx1'"])
+        .env("HOME", session.home())
+        .env("BAD", OsStr::from_bytes(b"a\xffb"))
+        .current_dir(session.cwd())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    // children get it untouched.
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "61 ff 62");
+}
+
+#[test]
+fn completing_names_with_spaces() {
+    use terminal_rs::core::{completion::TerminalHelper, editor::Helper};
+
+    let mut session = Session::new();
+    for dir in ["My Dir", "My Docs", "plain"] {
+        std::fs::create_dir(session.cwd().join(dir)).unwrap();
+    }
+    std::fs::write(session.cwd().join("My Dir").join("a \"b\""), "").unwrap();
+
+    let complete = |session: &mut Session, line: &str| {
+        let ctx = session.terminal.context();
+        let (start, candidates) = TerminalHelper::new(&ctx).complete(line, line.chars().count());
+        let values: Vec<String> = candidates.into_iter().map(|c| c.value).collect();
+        (start, values)
+    };
+
+    assert_eq!(complete(&mut session, "cd pl"), (3, vec!["plain/".to_string()]));
+    assert_eq!(complete(&mut session, "cd My"), (3, vec!["\"My Dir\"/".to_string(), "\"My Docs\"/".to_string()]));
+    assert_eq!(complete(&mut session, "cd \"My Di"), (3, vec!["\"My Dir\"/".to_string()]));
+    assert_eq!(complete(&mut session, "cat \"My Dir\"/a"), (4, vec!["\"My Dir/a \\\"b\\\"\"".to_string()]));
+
+    // and what they complete to runs.
+    let pwd = session.ok("cd \"My Dir\"/; pwd");
+    assert!(pwd.starts_with(&format!("locally: {}\n", session.cwd().join("My Dir").display())), "{}", pwd);
+}